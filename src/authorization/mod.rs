@@ -0,0 +1,233 @@
+use async_graphql::{Context, Error, Guard, Result};
+use axum::http::{header::AUTHORIZATION, HeaderMap};
+use bson::Uuid;
+use log::warn;
+use opentelemetry::KeyValue;
+use serde::Deserialize;
+
+use crate::telemetry::metrics::METRICS;
+
+use jwt::{JwtVerifier, JWT_VERIFIER};
+use permission::{Permission, ROLE_PERMISSIONS};
+
+pub mod jwt;
+pub mod permission;
+
+/// `Authorized-User` HTTP header.
+#[derive(Deserialize, Debug)]
+pub struct AuthorizedUserHeader {
+    /// UUID of the authorized user.
+    pub id: Uuid,
+    roles: Vec<Role>,
+}
+
+impl AuthorizedUserHeader {
+    /// Checks if one of the roles of the user grants a permission.
+    ///
+    /// * `permission` - Permission to check.
+    pub fn has_permission(&self, permission: Permission) -> bool {
+        self.roles.iter().any(|role| {
+            ROLE_PERMISSIONS
+                .get(role)
+                .is_some_and(|permissions| permissions.contains(&permission))
+        })
+    }
+
+    /// Checks if the user is the user of UUID or has a permission.
+    ///
+    /// * `id` - Option of UUID of the user to authorize.
+    /// * `permission` - Permission required if the user is not the user of UUID.
+    pub fn is_permitted(&self, id: Option<Uuid>, permission: Permission) -> bool {
+        let id_contained_in_header = id.map(|id| self.id == id).unwrap_or(false);
+        id_contained_in_header || self.has_permission(permission)
+    }
+
+    /// Extracts the authorized user from a header map.
+    ///
+    /// The `Authorized-User` header set by the gateway takes precedence.
+    /// Otherwise a bearer token in the `Authorization` header is verified if `$JWT_JWKS_PATH` is set.
+    ///
+    /// * `header_map` - Headers of the HTTP request.
+    pub fn from_headers(header_map: &HeaderMap) -> Result<Self> {
+        Self::from_headers_with(header_map, JWT_VERIFIER.as_ref())
+    }
+
+    /// Extracts the authorized user from a header map, verifying bearer tokens with a verifier.
+    ///
+    /// * `header_map` - Headers of the HTTP request.
+    /// * `jwt_verifier` - Verifier of bearer tokens, bearer tokens are ignored if `None`.
+    fn from_headers_with(
+        header_map: &HeaderMap,
+        jwt_verifier: Option<&JwtVerifier>,
+    ) -> Result<Self> {
+        if header_map.contains_key("Authorized-User") {
+            return Self::try_from(header_map);
+        }
+        if let Some(jwt_verifier) = jwt_verifier
+            && let Some(authorization_header_value) = header_map.get(AUTHORIZATION)
+            && let Ok(authorization_header_str) = authorization_header_value.to_str()
+            && let Some(token) = authorization_header_str.strip_prefix("Bearer ")
+        {
+            return jwt_verifier.verify(token.trim()).inspect_err(|error| {
+                METRICS
+                    .authorization_failures
+                    .add(1, &[KeyValue::new("reason", "invalid_token")]);
+                warn!("Bearer token rejected: {}", error.message);
+            });
+        }
+        Self::try_from(header_map)
+    }
+}
+
+/// Extraction of `Authorized-User` header from header map.
+impl TryFrom<&HeaderMap> for AuthorizedUserHeader {
+    type Error = Error;
+
+    /// Tries to extract the `Authorized-User` header from a header map.
+    ///
+    /// Returns a GraphQL error if the extraction fails.
+    fn try_from(header_map: &HeaderMap) -> Result<Self, Self::Error> {
+        if let Some(authorized_user_header_value) = header_map.get("Authorized-User")
+            && let Ok(authorized_user_header_str) = authorized_user_header_value.to_str()
+        {
+            let authorized_user_header: AuthorizedUserHeader =
+                serde_json::from_str(authorized_user_header_str)?;
+            return Ok(authorized_user_header);
+        }
+        Err(Error::new(
+            "Authorization failed. Authorized-User header is not set or could not be parsed.",
+        ))
+    }
+}
+
+/// Role of user.
+#[derive(Deserialize, Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Buyer,
+    Admin,
+    Employee,
+}
+
+/// Guard which only resolves a field for users with a permission.
+pub struct PermissionGuard(pub Permission);
+
+impl Guard for PermissionGuard {
+    async fn check(&self, ctx: &Context<'_>) -> Result<()> {
+        authorize_user(ctx, None, self.0)
+    }
+}
+
+/// Guard which only resolves a field for the user of UUID and users with a permission.
+pub struct UserGuard {
+    /// UUID of the user the field belongs to.
+    pub id: Uuid,
+    /// Permission required if the authorized user is not the user of UUID.
+    pub permission: Permission,
+    /// Flag if the guard is enforced, otherwise the field is resolved for all users.
+    pub is_enforced: bool,
+}
+
+impl UserGuard {
+    /// Creates a guard which is always enforced.
+    ///
+    /// * `id` - UUID of the user the field belongs to.
+    /// * `permission` - Permission required if the authorized user is not the user of UUID.
+    pub fn new(id: Uuid, permission: Permission) -> Self {
+        Self {
+            id,
+            permission,
+            is_enforced: true,
+        }
+    }
+
+    /// Only enforces the guard if a condition holds, e.g. if a review is anonymous.
+    ///
+    /// * `is_enforced` - Condition under which the guard is enforced.
+    pub fn when(self, is_enforced: bool) -> Self {
+        Self {
+            is_enforced,
+            ..self
+        }
+    }
+}
+
+impl Guard for UserGuard {
+    async fn check(&self, ctx: &Context<'_>) -> Result<()> {
+        if self.is_enforced {
+            authorize_user(ctx, Some(self.id), self.permission)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Authorize user of UUID for a context.
+///
+/// * `context` - GraphQL context containing the `Authorized-User` header.
+/// * `id` - Option of UUID of the user to authorize.
+/// * `permission` - Permission required if the authorized user is not the user of UUID.
+pub fn authorize_user(ctx: &Context, id: Option<Uuid>, permission: Permission) -> Result<()> {
+    let authorized_user_header = authorized_user(ctx)?;
+    check_permissions(authorized_user_header, id, permission)
+}
+
+/// Returns the `Authorized-User` header of a context.
+///
+/// * `context` - GraphQL context containing the `Authorized-User` header.
+pub fn authorized_user<'a>(ctx: &Context<'a>) -> Result<&'a AuthorizedUserHeader> {
+    match ctx.data::<AuthorizedUserHeader>() {
+        Ok(authorized_user_header) => Ok(authorized_user_header),
+        Err(_) => {
+            METRICS
+                .authorization_failures
+                .add(1, &[KeyValue::new("reason", "missing_header")]);
+            Err(Error::new(
+                "Authentication failed. Authorized-User header is not set or could not be parsed.",
+            ))
+        }
+    }
+}
+
+/// Checks if the user of a context is the user of UUID or has a permission, without failing.
+///
+/// Used to decide which data is visible to the caller.
+///
+/// * `context` - GraphQL context which may contain the `Authorized-User` header.
+/// * `id` - Option of UUID of the user to authorize.
+/// * `permission` - Permission required if the authorized user is not the user of UUID.
+pub fn is_authorized(ctx: &Context, id: Option<Uuid>, permission: Permission) -> bool {
+    ctx.data_opt::<AuthorizedUserHeader>()
+        .is_some_and(|authorized_user_header| authorized_user_header.is_permitted(id, permission))
+}
+
+/// Check if user of UUID has a valid permission according to the `Authorized-User` header.
+///
+/// Permission is valid if the user has the same UUID as provided in the function parameter.
+/// Permission is valid if one of the roles of the user grants the permission, regardless of the users UUID.
+///
+/// * `authorized_user_header` - `Authorized-User` header containing the users UUID and role.
+/// * `id` - Option of UUID of the user to authorize.
+/// * `permission` - Permission required if the authorized user is not the user of UUID.
+pub fn check_permissions(
+    authorized_user_header: &AuthorizedUserHeader,
+    id: Option<Uuid>,
+    permission: Permission,
+) -> Result<()> {
+    if authorized_user_header.is_permitted(id, permission) {
+        Ok(())
+    } else {
+        METRICS.authorization_failures.add(
+            1,
+            &[
+                KeyValue::new("reason", "not_permitted"),
+                KeyValue::new("permission", permission.as_str()),
+            ],
+        );
+        let message = format!(
+            "Authorization failed for user of UUID: `{}`. Operation requires permission: `{}`.",
+            authorized_user_header.id, permission
+        );
+        Err(Error::new(message))
+    }
+}
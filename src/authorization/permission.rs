@@ -0,0 +1,115 @@
+use std::{
+    collections::{HashMap, HashSet},
+    env, fmt, fs,
+};
+
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+
+use super::Role;
+
+/// Permissions of the roles, read from the JSON file at `$ROLE_PERMISSIONS_PATH` or defaulting to
+/// `default_role_permissions`.
+pub static ROLE_PERMISSIONS: Lazy<HashMap<Role, HashSet<Permission>>> =
+    Lazy::new(|| load_role_permissions().unwrap_or_else(|message| panic!("{}", message)));
+
+/// Permission to perform an operation on data of other users.
+///
+/// Users can always perform operations on their own reviews, permissions are only checked for data of other users.
+/// There is no permission to reply to reviews, since the service does not store replies.
+#[derive(Deserialize, Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Permission {
+    /// Create reviews on behalf of other users and update their body, rating and anonymity.
+    #[serde(rename = "review:write:any")]
+    WriteAnyReview,
+    /// Change the visibility of reviews, see hidden reviews and authors of anonymous reviews.
+    ///
+    /// Authors also need it to change the visibility of their own reviews.
+    /// Ordering reviews by author requires it, as the order would reveal the authors of anonymous reviews.
+    #[serde(rename = "review:moderate")]
+    ModerateReviews,
+    /// Delete reviews of other users.
+    #[serde(rename = "review:delete:any")]
+    DeleteAnyReview,
+    /// Export the review data of other users.
+    #[serde(rename = "review:export")]
+    ExportReviews,
+    /// Erase all data of a user.
+    #[serde(rename = "user-data:erase")]
+    EraseUserData,
+    /// Inspect and replay events which could not be processed.
+    #[serde(rename = "event:manage")]
+    ManageEvents,
+    /// List mirrored users, products and product variants with their review statistics.
+    #[serde(rename = "entity:list")]
+    ListEntities,
+}
+
+impl Permission {
+    /// All permissions.
+    pub const ALL: [Permission; 7] = [
+        Permission::WriteAnyReview,
+        Permission::ModerateReviews,
+        Permission::DeleteAnyReview,
+        Permission::ExportReviews,
+        Permission::EraseUserData,
+        Permission::ManageEvents,
+        Permission::ListEntities,
+    ];
+
+    /// Returns the name of the permission as used in the configuration.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::WriteAnyReview => "review:write:any",
+            Self::ModerateReviews => "review:moderate",
+            Self::DeleteAnyReview => "review:delete:any",
+            Self::ExportReviews => "review:export",
+            Self::EraseUserData => "user-data:erase",
+            Self::ManageEvents => "event:manage",
+            Self::ListEntities => "entity:list",
+        }
+    }
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Permissions of the roles if no configuration file is set.
+///
+/// Admins and employees have all permissions, buyers have no permissions.
+fn default_role_permissions() -> HashMap<Role, HashSet<Permission>> {
+    HashMap::from([
+        (Role::Admin, HashSet::from(Permission::ALL)),
+        (Role::Employee, HashSet::from(Permission::ALL)),
+        (Role::Buyer, HashSet::new()),
+    ])
+}
+
+/// Loads the permissions of the roles.
+///
+/// The configuration file maps role names to permission names, e.g. `{"employee": ["review:moderate"]}`.
+/// Roles missing in the file have no permissions.
+fn load_role_permissions() -> Result<HashMap<Role, HashSet<Permission>>, String> {
+    match env::var_os("ROLE_PERMISSIONS_PATH") {
+        Some(path) => {
+            let content = fs::read_to_string(&path).map_err(|error| {
+                format!(
+                    "Role permissions: `{}` could not be read: {}",
+                    path.to_string_lossy(),
+                    error
+                )
+            })?;
+            serde_json::from_str(&content).map_err(|error| {
+                format!(
+                    "Role permissions: `{}` could not be parsed: {}",
+                    path.to_string_lossy(),
+                    error
+                )
+            })
+        }
+        None => Ok(default_role_permissions()),
+    }
+}
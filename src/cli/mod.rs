@@ -0,0 +1,4 @@
+pub mod resync;
+pub mod review_export;
+pub mod review_file_format;
+pub mod review_import;
@@ -0,0 +1,303 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    error::Error,
+    fs,
+    path::Path,
+};
+
+use bson::{doc, Uuid};
+use clap::ValueEnum;
+use futures::TryStreamExt;
+use log::info;
+use mongodb::{Collection, Database};
+use serde::{de::DeserializeOwned, Deserialize};
+
+use crate::{
+    event::{
+        erased_user::find_erased,
+        http_event_service::{
+            create_in_mongodb, create_user_in_mongodb, update_product_variant_in_mongodb,
+            EventData, HttpEventServiceState, ProductVariantEventData,
+        },
+    },
+    graphql::model::{product::Product, product_variant::ProductVariant, user::User},
+};
+
+/// Number of dump entities compared with the local collection per query.
+const RECONCILIATION_BATCH_SIZE: usize = 1000;
+
+/// Format of a dump file containing mirrored entities.
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum DumpFormat {
+    /// JSON object with the arrays `users`, `products` and `productVariants`.
+    Json,
+    /// One JSON object per line, tagged with `entity`: `user`, `product` or `productVariant`.
+    Ndjson,
+}
+
+impl DumpFormat {
+    /// Infers the format from the file extension, `.ndjson` and `.jsonl` are NDJSON, all others JSON.
+    ///
+    /// * `path` - Path of the dump file.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("ndjson") | Some("jsonl") => Self::Ndjson,
+            _ => Self::Json,
+        }
+    }
+}
+
+/// Mirrored entities of a dump file in JSON format.
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct EntityDump {
+    users: Vec<EventData>,
+    products: Vec<EventData>,
+    product_variants: Vec<ProductVariantEventData>,
+}
+
+/// Line of a dump file in NDJSON format.
+#[derive(Deserialize)]
+#[serde(tag = "entity", rename_all = "camelCase")]
+enum DumpRecord {
+    User(EventData),
+    Product(EventData),
+    ProductVariant(ProductVariantEventData),
+}
+
+/// Entity of a dump file.
+trait DumpEntity {
+    /// UUID of the entity.
+    fn id(&self) -> Uuid;
+}
+
+impl DumpEntity for EventData {
+    fn id(&self) -> Uuid {
+        self.id
+    }
+}
+
+impl DumpEntity for ProductVariantEventData {
+    fn id(&self) -> Uuid {
+        self.id
+    }
+}
+
+/// Counts of a reconciliation of a dump with a local collection.
+#[derive(Debug, Default)]
+pub struct ReconciliationReport {
+    /// Entities of the dump which were missing locally.
+    pub inserted: usize,
+    /// Entities of the dump which differed from the local entity.
+    pub updated: usize,
+    /// Entities of the dump which are identical to the local entity.
+    pub unchanged: usize,
+    /// Local entities which are not part of the dump, they are kept since reviews may reference them.
+    pub orphaned: u64,
+    /// Users of the dump whose data has been erased, they are not mirrored again.
+    pub erased: usize,
+}
+
+/// Result of comparing a dump with a local collection.
+struct Reconciliation<T> {
+    report: ReconciliationReport,
+    /// Entities which need to be inserted or updated.
+    changed: Vec<T>,
+}
+
+/// Imports users, products and product variants from a dump file and reconciles them with the local collections.
+///
+/// Used if the service missed events, e.g. because it was deployed after the entities were created.
+///
+/// * `db_client` - MongoDB database client.
+/// * `path` - Path of the dump file.
+/// * `format` - Format of the dump file, inferred from the file extension if `None`.
+pub async fn resync(
+    db_client: &Database,
+    path: &Path,
+    format: Option<DumpFormat>,
+) -> Result<(), Box<dyn Error>> {
+    let format = format.unwrap_or_else(|| DumpFormat::from_path(path));
+    let content = fs::read_to_string(path)?;
+    let dump = match format {
+        DumpFormat::Json => serde_json::from_str(&content)?,
+        DumpFormat::Ndjson => parse_ndjson(&content)?,
+    };
+    let state = HttpEventServiceState::new(db_client);
+
+    let (users, erased_count) = remove_erased_users(&state, dump.users).await?;
+    let mut reconciliation = reconcile(
+        &state.user_collection,
+        users,
+        |user| user._id,
+        |user, _| User::from(user.id),
+    )
+    .await?;
+    reconciliation.report.erased = erased_count;
+    for user in reconciliation.changed {
+        create_user_in_mongodb(&state, user._id).await?;
+    }
+    log_report("users", &reconciliation.report);
+
+    let reconciliation = reconcile(
+        &state.product_collection,
+        dump.products,
+        |product| product._id,
+        |product, _| Product::from(product.id),
+    )
+    .await?;
+    for product in reconciliation.changed {
+        create_in_mongodb(&state.product_collection, product._id).await?;
+    }
+    log_report("products", &reconciliation.report);
+
+    let reconciliation = reconcile(
+        &state.product_variant_collection,
+        dump.product_variants,
+        |product_variant| product_variant._id,
+        merge_product_variant,
+    )
+    .await?;
+    for product_variant in reconciliation.changed {
+        update_product_variant_in_mongodb(&state, product_variant).await?;
+    }
+    log_report("product variants", &reconciliation.report);
+    Ok(())
+}
+
+/// Removes the users whose data has been erased from the users of a dump.
+///
+/// Returns the remaining users and the number of removed users.
+///
+/// * `state` - Service state containing database connections.
+/// * `users` - Users of the dump.
+async fn remove_erased_users(
+    state: &HttpEventServiceState,
+    users: Vec<EventData>,
+) -> Result<(Vec<EventData>, usize), Box<dyn Error>> {
+    let ids: Vec<Uuid> = users.iter().map(|user| user.id).collect();
+    let mut erased_ids = HashSet::new();
+    for batch in ids.chunks(RECONCILIATION_BATCH_SIZE) {
+        erased_ids.extend(find_erased(&state.erased_user_collection, batch).await?);
+    }
+    let (erased_users, remaining_users): (Vec<EventData>, Vec<EventData>) = users
+        .into_iter()
+        .partition(|user| erased_ids.contains(&user.id));
+    Ok((remaining_users, erased_users.len()))
+}
+
+/// Creates the product variant of a dump entity.
+///
+/// A dump entity without `is_active` keeps the flag of the local product variant,
+/// so that a resync does not reactivate product variants disabled by the catalog.
+///
+/// * `product_variant_data` - Product variant of the dump.
+/// * `maybe_local_product_variant` - Local product variant of the same UUID if it exists.
+fn merge_product_variant(
+    product_variant_data: &ProductVariantEventData,
+    maybe_local_product_variant: Option<&ProductVariant>,
+) -> ProductVariant {
+    let mut product_variant = ProductVariant::from(product_variant_data);
+    if product_variant_data.is_active.is_none()
+        && let Some(local_product_variant) = maybe_local_product_variant
+    {
+        product_variant.is_active = local_product_variant.is_active;
+    }
+    product_variant
+}
+
+/// Parses a dump file in NDJSON format, skipping empty lines.
+///
+/// * `content` - Content of the dump file.
+fn parse_ndjson(content: &str) -> Result<EntityDump, Box<dyn Error>> {
+    let mut dump = EntityDump::default();
+    for (index, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str(line).map_err(|error| {
+            format!("Line {} of dump could not be parsed: {}", index + 1, error)
+        })?;
+        match record {
+            DumpRecord::User(user) => dump.users.push(user),
+            DumpRecord::Product(product) => dump.products.push(product),
+            DumpRecord::ProductVariant(product_variant) => {
+                dump.product_variants.push(product_variant)
+            }
+        }
+    }
+    Ok(dump)
+}
+
+/// Compares the entities of a dump with a local collection.
+///
+/// Compares the dump in batches of `RECONCILIATION_BATCH_SIZE` entities sorted by UUID,
+/// so that only the local entities of a batch are held in memory.
+/// If an entity occurs multiple times in the dump, the last occurrence is used.
+///
+/// * `collection` - MongoDB collection of the mirrored entities.
+/// * `entities` - Entities of the dump.
+/// * `id_of` - Returns the UUID of a local entity.
+/// * `to_entity` - Creates the local entity of a dump entity, given the local entity of the same UUID if it exists.
+async fn reconcile<D, T>(
+    collection: &Collection<T>,
+    entities: Vec<D>,
+    id_of: fn(&T) -> Uuid,
+    to_entity: fn(&D, Option<&T>) -> T,
+) -> Result<Reconciliation<T>, Box<dyn Error>>
+where
+    D: DumpEntity,
+    T: DeserializeOwned + PartialEq + Unpin + Send + Sync,
+{
+    let dump_entities: BTreeMap<Uuid, D> = entities
+        .into_iter()
+        .map(|entity| (entity.id(), entity))
+        .collect();
+    let ids: Vec<Uuid> = dump_entities.keys().copied().collect();
+    let local_count = collection.count_documents(None, None).await?;
+    let mut report = ReconciliationReport::default();
+    let mut changed = Vec::new();
+    let mut matched_count = 0;
+    for batch in ids.chunks(RECONCILIATION_BATCH_SIZE) {
+        let local_entities: HashMap<Uuid, T> = collection
+            .find(doc! {"_id": {"$in": batch}}, None)
+            .await?
+            .map_ok(|entity| (id_of(&entity), entity))
+            .try_collect()
+            .await?;
+        for id in batch {
+            let maybe_local_entity = local_entities.get(id);
+            let entity = to_entity(&dump_entities[id], maybe_local_entity);
+            match maybe_local_entity {
+                Some(local_entity) if *local_entity == entity => report.unchanged += 1,
+                Some(_) => {
+                    report.updated += 1;
+                    changed.push(entity);
+                }
+                None => {
+                    report.inserted += 1;
+                    changed.push(entity);
+                }
+            }
+        }
+        matched_count += local_entities.len() as u64;
+    }
+    report.orphaned = local_count.saturating_sub(matched_count);
+    Ok(Reconciliation { report, changed })
+}
+
+/// Logs the counts of a reconciliation.
+///
+/// * `entity_name` - Name of the reconciled entities.
+/// * `report` - Counts of the reconciliation.
+fn log_report(entity_name: &str, report: &ReconciliationReport) {
+    info!(
+        "Resynced {}: {} inserted, {} updated, {} unchanged, {} orphaned, {} erased.",
+        entity_name,
+        report.inserted,
+        report.updated,
+        report.unchanged,
+        report.orphaned,
+        report.erased
+    );
+}
@@ -0,0 +1,130 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use bson::{doc, DateTime, Document, Uuid};
+use clap::Args;
+use futures::TryStreamExt;
+use log::info;
+use mongodb::{options::FindOptions, Collection, Database};
+
+use super::review_file_format::ReviewFileFormat;
+use crate::graphql::model::{review::Review, review_record::ReviewRecord};
+
+/// Filters of a review export.
+#[derive(Args, Debug, Default)]
+pub struct ReviewExportFilter {
+    /// Only exports reviews of the user with this UUID.
+    #[arg(long, value_parser = parse_uuid)]
+    pub user_id: Option<Uuid>,
+    /// Only exports reviews of the product with this UUID.
+    #[arg(long, value_parser = parse_uuid)]
+    pub product_id: Option<Uuid>,
+    /// Only exports reviews of the product variant with this UUID.
+    #[arg(long, value_parser = parse_uuid)]
+    pub product_variant_id: Option<Uuid>,
+    /// Only exports reviews created at or after this RFC 3339 timestamp.
+    #[arg(long, value_parser = parse_timestamp)]
+    pub created_after: Option<DateTime>,
+    /// Only exports reviews created before this RFC 3339 timestamp.
+    #[arg(long, value_parser = parse_timestamp)]
+    pub created_before: Option<DateTime>,
+    /// Only exports visible reviews.
+    #[arg(long)]
+    pub visible_only: bool,
+}
+
+impl ReviewExportFilter {
+    /// Builds the MongoDB filter of the export.
+    fn to_document(&self) -> Document {
+        let mut filter = doc! {};
+        if let Some(definitely_user_id) = self.user_id {
+            filter.insert("user._id", definitely_user_id);
+        }
+        if let Some(definitely_product_id) = self.product_id {
+            filter.insert("product_variant.product_id", definitely_product_id);
+        }
+        if let Some(definitely_product_variant_id) = self.product_variant_id {
+            filter.insert("product_variant._id", definitely_product_variant_id);
+        }
+        let mut created_at_filter = doc! {};
+        if let Some(definitely_created_after) = self.created_after {
+            created_at_filter.insert("$gte", definitely_created_after);
+        }
+        if let Some(definitely_created_before) = self.created_before {
+            created_at_filter.insert("$lt", definitely_created_before);
+        }
+        if !created_at_filter.is_empty() {
+            filter.insert("created_at", created_at_filter);
+        }
+        if self.visible_only {
+            filter.insert("is_visible", true);
+        }
+        filter
+    }
+}
+
+/// Exports reviews matching a filter to a file, oldest reviews first.
+///
+/// * `db_client` - MongoDB database client.
+/// * `path` - Path of the export file.
+/// * `format` - Format of the export file, inferred from the file extension if `None`.
+/// * `filter` - Filter of the exported reviews.
+pub async fn export_reviews(
+    db_client: &Database,
+    path: &Path,
+    format: Option<ReviewFileFormat>,
+    filter: &ReviewExportFilter,
+) -> Result<(), Box<dyn Error>> {
+    let format = format.unwrap_or_else(|| ReviewFileFormat::from_path(path));
+    let collection: Collection<Review> = db_client.collection::<Review>("reviews");
+    let find_options = FindOptions::builder()
+        .sort(doc! {"created_at": 1, "_id": 1})
+        .build();
+    let mut cursor = collection.find(filter.to_document(), find_options).await?;
+    let file = File::create(path)?;
+    let mut exported_count = 0;
+    match format {
+        ReviewFileFormat::Ndjson => {
+            let mut writer = BufWriter::new(file);
+            while let Some(review) = cursor.try_next().await? {
+                serde_json::to_writer(&mut writer, &ReviewRecord::from(&review))?;
+                writer.write_all(b"\n")?;
+                exported_count += 1;
+            }
+            writer.flush()?;
+        }
+        ReviewFileFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(file);
+            while let Some(review) = cursor.try_next().await? {
+                writer.serialize(ReviewRecord::from(&review))?;
+                exported_count += 1;
+            }
+            writer.flush()?;
+        }
+    }
+    info!(
+        "Exported {} reviews to `{}`.",
+        exported_count,
+        path.display()
+    );
+    Ok(())
+}
+
+/// Parses a UUID command line argument.
+///
+/// * `value` - Value of the argument.
+fn parse_uuid(value: &str) -> Result<Uuid, String> {
+    Uuid::parse_str(value).map_err(|error| format!("`{}` is not a valid UUID: {}", value, error))
+}
+
+/// Parses an RFC 3339 timestamp command line argument.
+///
+/// * `value` - Value of the argument.
+fn parse_timestamp(value: &str) -> Result<DateTime, String> {
+    DateTime::parse_rfc3339_str(value)
+        .map_err(|error| format!("`{}` is not a valid RFC 3339 timestamp: {}", value, error))
+}
@@ -0,0 +1,24 @@
+use std::path::Path;
+
+use clap::ValueEnum;
+
+/// Format of a review import or export file.
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum ReviewFileFormat {
+    /// One JSON object per line.
+    Ndjson,
+    /// Comma-separated values with a header row.
+    Csv,
+}
+
+impl ReviewFileFormat {
+    /// Infers the format from the file extension, `.csv` is CSV, all others NDJSON.
+    ///
+    /// * `path` - Path of the review file.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("csv") => Self::Csv,
+            _ => Self::Ndjson,
+        }
+    }
+}
@@ -0,0 +1,356 @@
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use bson::{doc, DateTime, Uuid};
+use futures::TryStreamExt;
+use log::{info, warn};
+use mongodb::{options::FindOptions, Collection, Database};
+use serde::{Deserialize, Serialize};
+
+use super::review_file_format::ReviewFileFormat;
+use crate::graphql::model::{
+    product_variant::ProductVariant,
+    review::{validate_media_urls, Rating, Review},
+    review_record::ReviewRecord,
+    user::User,
+};
+
+/// Number of reviews inserted into MongoDB per batch.
+const INSERT_BATCH_SIZE: usize = 1000;
+
+/// Record of an import file with its record number, records which can not be parsed are kept as error.
+type NumberedRecord = (usize, Result<ReviewRecord, String>);
+
+/// Identifying fields of a review already stored in MongoDB.
+#[derive(Deserialize)]
+struct ExistingReview {
+    _id: Uuid,
+    user: EntityReference,
+    product_variant: EntityReference,
+}
+
+/// Reference to a user or product variant embedded in a review.
+#[derive(Deserialize)]
+struct EntityReference {
+    _id: Uuid,
+}
+
+/// Kind of a rejected record.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+enum ImportErrorKind {
+    /// The record could not be parsed or contains invalid values.
+    Invalid,
+    /// A review of the same id or the same user and product variant already exists.
+    Duplicate,
+}
+
+/// Entry of the error report, describing why a record was rejected.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ImportError {
+    /// Line of the record in NDJSON files, number of the data row in CSV files.
+    record: usize,
+    kind: ImportErrorKind,
+    error: String,
+}
+
+/// Users, product variants and reviews of MongoDB the imported reviews are validated against.
+struct ImportContext {
+    user_ids: HashSet<Uuid>,
+    product_variants: HashMap<Uuid, ProductVariant>,
+    review_ids: HashSet<Uuid>,
+    /// Pairs of user UUID and product variant UUID which already have a review.
+    reviewed_product_variants: HashSet<(Uuid, Uuid)>,
+}
+
+/// Imports reviews from a file.
+///
+/// Validates every record against the mirrored users and product variants and rejects reviews which
+/// already exist for the same user and product variant.
+/// Rejected records are written to an error report, valid reviews are inserted unless `dry_run` is set.
+///
+/// * `db_client` - MongoDB database client.
+/// * `path` - Path of the import file.
+/// * `format` - Format of the import file, inferred from the file extension if `None`.
+/// * `dry_run` - Only validates the reviews without inserting them.
+/// * `error_report_path` - Path of the error report, by default `<path>.errors.ndjson`.
+pub async fn import_reviews(
+    db_client: &Database,
+    path: &Path,
+    format: Option<ReviewFileFormat>,
+    dry_run: bool,
+    error_report_path: Option<PathBuf>,
+) -> Result<(), Box<dyn Error>> {
+    let format = format.unwrap_or_else(|| ReviewFileFormat::from_path(path));
+    let records = read_records(path, format)?;
+    let review_collection: Collection<Review> = db_client.collection::<Review>("reviews");
+    let mut import_context = load_import_context(db_client).await?;
+    let mut reviews = Vec::new();
+    let mut import_errors = Vec::new();
+    let import_timestamp = DateTime::now();
+    for (record_number, maybe_record) in records {
+        let result = maybe_record
+            .map_err(|error| (ImportErrorKind::Invalid, error))
+            .and_then(|record| import_context.validate(record, import_timestamp));
+        match result {
+            Ok(review) => reviews.push(review),
+            Err((kind, error)) => import_errors.push(ImportError {
+                record: record_number,
+                kind,
+                error,
+            }),
+        }
+    }
+    if !dry_run {
+        for batch in reviews.chunks(INSERT_BATCH_SIZE) {
+            review_collection.insert_many(batch, None).await?;
+        }
+    }
+    let duplicate_count = import_errors
+        .iter()
+        .filter(|import_error| import_error.kind == ImportErrorKind::Duplicate)
+        .count();
+    info!(
+        "{} {} reviews from `{}`, rejected {} duplicate and {} invalid records.",
+        if dry_run { "Validated" } else { "Imported" },
+        reviews.len(),
+        path.display(),
+        duplicate_count,
+        import_errors.len() - duplicate_count
+    );
+    if !import_errors.is_empty() {
+        let error_report_path = error_report_path
+            .unwrap_or_else(|| PathBuf::from(format!("{}.errors.ndjson", path.display())));
+        write_error_report(&error_report_path, &import_errors)?;
+        warn!(
+            "Rejected records are described in `{}`.",
+            error_report_path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Reads the records of an import file together with their record number.
+///
+/// Records which can not be parsed are returned as error, so that they can be reported.
+///
+/// * `path` - Path of the import file.
+/// * `format` - Format of the import file.
+fn read_records(
+    path: &Path,
+    format: ReviewFileFormat,
+) -> Result<Vec<NumberedRecord>, Box<dyn Error>> {
+    let records = match format {
+        ReviewFileFormat::Ndjson => fs::read_to_string(path)?
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| {
+                let record = serde_json::from_str(line)
+                    .map_err(|error| format!("Record could not be parsed: {}", error));
+                (index + 1, record)
+            })
+            .collect(),
+        ReviewFileFormat::Csv => csv::Reader::from_path(path)?
+            .deserialize()
+            .enumerate()
+            .map(|(index, record)| {
+                let record =
+                    record.map_err(|error| format!("Record could not be parsed: {}", error));
+                (index + 1, record)
+            })
+            .collect(),
+    };
+    Ok(records)
+}
+
+/// Loads the users, product variants and existing reviews to validate imported reviews against.
+///
+/// * `db_client` - MongoDB database client.
+async fn load_import_context(db_client: &Database) -> Result<ImportContext, Box<dyn Error>> {
+    let users: Vec<User> = db_client
+        .collection::<User>("users")
+        .find(None, None)
+        .await?
+        .try_collect()
+        .await?;
+    let product_variants: Vec<ProductVariant> = db_client
+        .collection::<ProductVariant>("product_variants")
+        .find(None, None)
+        .await?
+        .try_collect()
+        .await?;
+    let find_options = FindOptions::builder()
+        .projection(doc! {"_id": 1, "user._id": 1, "product_variant._id": 1})
+        .build();
+    let existing_reviews: Vec<ExistingReview> = db_client
+        .collection::<ExistingReview>("reviews")
+        .find(None, find_options)
+        .await?
+        .try_collect()
+        .await?;
+    Ok(ImportContext {
+        user_ids: users.into_iter().map(|user| user._id).collect(),
+        product_variants: product_variants
+            .into_iter()
+            .map(|product_variant| (product_variant._id, product_variant))
+            .collect(),
+        review_ids: existing_reviews.iter().map(|review| review._id).collect(),
+        reviewed_product_variants: existing_reviews
+            .iter()
+            .map(|review| (review.user._id, review.product_variant._id))
+            .collect(),
+    })
+}
+
+impl ImportContext {
+    /// Validates a record and converts it to a review.
+    ///
+    /// Registers the review, so that later records of the same review are rejected as duplicates.
+    ///
+    /// * `record` - Record of the import file.
+    /// * `import_timestamp` - Timestamp used if the record has no creation timestamp.
+    fn validate(
+        &mut self,
+        record: ReviewRecord,
+        import_timestamp: DateTime,
+    ) -> Result<Review, (ImportErrorKind, String)> {
+        let invalid = |error: String| (ImportErrorKind::Invalid, error);
+        let id = match &record.id {
+            Some(definitely_id) if !definitely_id.trim().is_empty() => {
+                parse_uuid("id", definitely_id).map_err(invalid)?
+            }
+            _ => Uuid::new(),
+        };
+        let user_id = parse_uuid("userId", &record.user_id).map_err(invalid)?;
+        if !self.user_ids.contains(&user_id) {
+            let message = format!(
+                "User with the UUID: `{}` is not present in the system.",
+                user_id
+            );
+            return Err(invalid(message));
+        }
+        let product_variant_id =
+            parse_uuid("productVariantId", &record.product_variant_id).map_err(invalid)?;
+        let product_variant = match self.product_variants.get(&product_variant_id) {
+            Some(product_variant) if !product_variant.is_active => {
+                let message = format!(
+                    "Product variant with the UUID: `{}` is inactive and can not be reviewed.",
+                    product_variant_id
+                );
+                return Err(invalid(message));
+            }
+            Some(product_variant) => product_variant.clone(),
+            None => {
+                let message = format!(
+                    "Product variant with the UUID: `{}` is not present in the system.",
+                    product_variant_id
+                );
+                return Err(invalid(message));
+            }
+        };
+        let rating = record.rating.parse::<Rating>().map_err(invalid)?;
+        let media_urls: Vec<String> = record
+            .media_urls
+            .as_deref()
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(str::to_string)
+            .collect();
+        validate_media_urls(&media_urls).map_err(invalid)?;
+        let created_at = parse_optional_timestamp("createdAt", &record.created_at)
+            .map_err(invalid)?
+            .unwrap_or(import_timestamp);
+        let last_updated_at = parse_optional_timestamp("lastUpdatedAt", &record.last_updated_at)
+            .map_err(invalid)?
+            .unwrap_or(created_at);
+        if last_updated_at < created_at {
+            let message = "`lastUpdatedAt` must not be before `createdAt`.".to_string();
+            return Err(invalid(message));
+        }
+        if self.review_ids.contains(&id) {
+            let message = format!("Review with the UUID: `{}` already exists.", id);
+            return Err((ImportErrorKind::Duplicate, message));
+        }
+        if self
+            .reviewed_product_variants
+            .contains(&(user_id, product_variant_id))
+        {
+            let message = format!(
+                "User of UUID: `{}` has already written a review for product variant of UUID: `{}`.",
+                user_id, product_variant_id
+            );
+            return Err((ImportErrorKind::Duplicate, message));
+        }
+        self.review_ids.insert(id);
+        self.reviewed_product_variants
+            .insert((user_id, product_variant_id));
+        Ok(Review {
+            _id: id,
+            user: User { _id: user_id },
+            product_variant,
+            body: record.body,
+            rating,
+            created_at,
+            last_updated_at,
+            is_visible: record.is_visible.unwrap_or(true),
+            is_anonymous: record.is_anonymous.unwrap_or(false),
+            helpful_vote_count: 0,
+            unhelpful_vote_count: 0,
+            is_verified_purchase: record.is_verified_purchase.unwrap_or(false),
+            media_urls,
+        })
+    }
+}
+
+/// Writes the rejected records as NDJSON.
+///
+/// * `path` - Path of the error report.
+/// * `import_errors` - Rejected records.
+fn write_error_report(path: &Path, import_errors: &[ImportError]) -> Result<(), Box<dyn Error>> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    for import_error in import_errors {
+        serde_json::to_writer(&mut writer, import_error)?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Parses a UUID field of a record.
+///
+/// * `field` - Name of the field.
+/// * `value` - Value of the field.
+fn parse_uuid(field: &str, value: &str) -> Result<Uuid, String> {
+    Uuid::parse_str(value.trim())
+        .map_err(|_| format!("`{}`: `{}` is not a valid UUID.", field, value))
+}
+
+/// Parses an optional RFC 3339 timestamp field of a record, empty values are treated as missing.
+///
+/// * `field` - Name of the field.
+/// * `value` - Value of the field.
+fn parse_optional_timestamp(
+    field: &str,
+    value: &Option<String>,
+) -> Result<Option<DateTime>, String> {
+    match value {
+        Some(definitely_value) if !definitely_value.trim().is_empty() => {
+            DateTime::parse_rfc3339_str(definitely_value.trim())
+                .map(Some)
+                .map_err(|_| {
+                    format!(
+                        "`{}`: `{}` is not a valid RFC 3339 timestamp.",
+                        field, definitely_value
+                    )
+                })
+        }
+        _ => Ok(None),
+    }
+}
@@ -0,0 +1,440 @@
+use std::convert::Infallible;
+
+use axum::{
+    body::Bytes,
+    extract::{FromRequest, Request},
+    http::{header::CONTENT_TYPE, HeaderMap},
+};
+use bson::DateTime;
+use log::info;
+use opentelemetry::{propagation::Extractor, Context};
+use serde::{de::DeserializeOwned, Deserialize};
+
+use super::event_error::EventError;
+use crate::{
+    logging::LogContext,
+    telemetry::propagation::{extract_context, start_event_span, HeaderExtractor},
+};
+
+/// CloudEvents specification version supported by the service.
+const SUPPORTED_SPEC_VERSION: &str = "1.0";
+
+/// Prefix of HTTP headers carrying CloudEvent attributes in binary content mode.
+const BINARY_MODE_HEADER_PREFIX: &str = "ce-";
+
+/// Dapr event wrapped in a CloudEvents 1.0 envelope.
+#[derive(Deserialize, Debug)]
+pub struct Event<T> {
+    /// Id of the event, unique per source.
+    pub id: String,
+    /// Context in which the event happened, e.g. the publishing app id.
+    pub source: String,
+    /// Type of the event, e.g. `com.dapr.event.sent`.
+    #[serde(rename = "type")]
+    pub event_type: String,
+    /// Version of the CloudEvents specification used by the event.
+    pub specversion: String,
+    /// Timestamp when the event happened in RFC 3339 format.
+    pub time: Option<String>,
+    /// Content type of `data`.
+    pub datacontenttype: Option<String>,
+    /// Topic the event was published on, set by Dapr.
+    pub topic: String,
+    /// Name of the pub/sub component the event was published on, set by Dapr.
+    pub pubsubname: Option<String>,
+    /// Payload of the event.
+    pub data: T,
+    /// W3C trace parent of the publisher, set by Dapr in the cloud envelope.
+    pub traceparent: Option<String>,
+    /// W3C trace state of the publisher, set by Dapr in the cloud envelope.
+    pub tracestate: Option<String>,
+}
+
+/// Extraction of W3C trace context from the cloud envelope.
+impl<T> Extractor for Event<T> {
+    fn get(&self, key: &str) -> Option<&str> {
+        match key {
+            "traceparent" => self.traceparent.as_deref(),
+            "tracestate" => self.tracestate.as_deref(),
+            _ => None,
+        }
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec!["traceparent", "tracestate"]
+    }
+}
+
+impl<T: DeserializeOwned> Event<T> {
+    /// Parses an event delivered in structured content mode, where the body contains the whole envelope as JSON.
+    ///
+    /// * `body` - Body of the HTTP request.
+    fn from_structured(body: &[u8]) -> Result<Self, EventError> {
+        serde_json::from_slice(body).map_err(|error| {
+            EventError::Invalid(format!(
+                "CloudEvent envelope could not be parsed: {}",
+                error
+            ))
+        })
+    }
+
+    /// Parses an event delivered in binary content mode, where the attributes are `ce-` prefixed HTTP headers
+    /// and the body contains only the data.
+    ///
+    /// * `headers` - Headers of the HTTP request.
+    /// * `body` - Body of the HTTP request.
+    fn from_binary(headers: &HeaderMap, body: &[u8]) -> Result<Self, EventError> {
+        let data = serde_json::from_slice(body).map_err(|error| {
+            EventError::Invalid(format!("CloudEvent data could not be parsed: {}", error))
+        })?;
+        Ok(Self {
+            id: required_attribute(headers, "id")?,
+            source: required_attribute(headers, "source")?,
+            event_type: required_attribute(headers, "type")?,
+            specversion: required_attribute(headers, "specversion")?,
+            time: attribute(headers, "time"),
+            datacontenttype: headers
+                .get(CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.to_string()),
+            topic: required_attribute(headers, "topic")?,
+            pubsubname: attribute(headers, "pubsubname"),
+            data,
+            traceparent: attribute(headers, "traceparent"),
+            tracestate: attribute(headers, "tracestate"),
+        })
+    }
+}
+
+impl<T> Event<T> {
+    /// Validates the envelope against the CloudEvents 1.0 specification.
+    fn validate(&self) -> Result<(), EventError> {
+        if self.specversion != SUPPORTED_SPEC_VERSION {
+            let message = format!(
+                "CloudEvent spec version `{}` is not supported, expected `{}`.",
+                self.specversion, SUPPORTED_SPEC_VERSION
+            );
+            return Err(EventError::Invalid(message));
+        }
+        for (name, value) in [
+            ("id", &self.id),
+            ("source", &self.source),
+            ("type", &self.event_type),
+            ("topic", &self.topic),
+        ] {
+            if value.is_empty() {
+                let message = format!("CloudEvent attribute `{}` must not be empty.", name);
+                return Err(EventError::Invalid(message));
+            }
+        }
+        if let Some(definitely_time) = &self.time
+            && DateTime::parse_rfc3339_str(definitely_time).is_err()
+        {
+            let message = format!(
+                "CloudEvent attribute `time`: `{}` is not a valid RFC 3339 timestamp.",
+                definitely_time
+            );
+            return Err(EventError::Invalid(message));
+        }
+        if let Some(definitely_datacontenttype) = &self.datacontenttype
+            && !is_json_content_type(definitely_datacontenttype)
+        {
+            let message = format!(
+                "CloudEvent data content type `{}` is not supported, expected JSON.",
+                definitely_datacontenttype
+            );
+            return Err(EventError::Invalid(message));
+        }
+        Ok(())
+    }
+
+    /// Logs the envelope attributes of the event.
+    pub fn log_envelope(&self) {
+        info!(
+            "Received event `{}` of type `{}` from `{}` on topic `{}` of pub/sub `{}` (spec version: `{}`, time: `{}`, data content type: `{}`).",
+            self.id,
+            self.event_type,
+            self.source,
+            self.topic,
+            self.pubsubname.as_deref().unwrap_or("-"),
+            self.specversion,
+            self.time.as_deref().unwrap_or("-"),
+            self.datacontenttype.as_deref().unwrap_or("-"),
+        );
+    }
+
+    /// Starts a span for processing the event and returns the context containing it.
+    ///
+    /// The trace context of the cloud envelope takes precedence over the HTTP headers of the request.
+    ///
+    /// * `headers` - Headers of the HTTP request delivering the event.
+    pub fn start_span(&self, headers: &HeaderMap) -> Context {
+        let parent_context = match self.traceparent {
+            Some(_) => extract_context(self),
+            None => extract_context(&HeaderExtractor(headers)),
+        };
+        start_event_span(&parent_context, &self.topic)
+    }
+
+    /// Creates the log context of the event containing its id and topic.
+    ///
+    /// * `headers` - Headers of the HTTP request delivering the event.
+    pub fn log_context(&self, headers: &HeaderMap) -> LogContext {
+        LogContext {
+            event_id: Some(self.id.clone()),
+            topic: Some(self.topic.clone()),
+            ..LogContext::from_headers(headers)
+        }
+    }
+}
+
+/// Extractor of a CloudEvent in structured or binary content mode.
+///
+/// Never rejects the request, so that invalid events can be answered with a Dapr status instead of an HTTP error.
+pub struct ReceivedEvent<T>(pub Result<Event<T>, EventError>);
+
+impl<S, T> FromRequest<S> for ReceivedEvent<T>
+where
+    S: Send + Sync,
+    T: DeserializeOwned,
+{
+    type Rejection = Infallible;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let headers = req.headers().clone();
+        let body = match Bytes::from_request(req, state).await {
+            Ok(body) => body,
+            Err(rejection) => {
+                let message = format!("Request body could not be read: {}", rejection);
+                return Ok(Self(Err(EventError::Invalid(message))));
+            }
+        };
+        let binary_mode = headers.contains_key(format!("{}specversion", BINARY_MODE_HEADER_PREFIX));
+        let event = if binary_mode {
+            Event::from_binary(&headers, &body)
+        } else {
+            Event::from_structured(&body)
+        };
+        Ok(Self(
+            event.and_then(|event| event.validate().map(|_| event)),
+        ))
+    }
+}
+
+/// Returns the value of a CloudEvent attribute in binary content mode.
+///
+/// * `headers` - Headers of the HTTP request.
+/// * `name` - Name of the attribute without prefix.
+fn attribute(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get(format!("{}{}", BINARY_MODE_HEADER_PREFIX, name))
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string())
+}
+
+/// Returns the value of a required CloudEvent attribute in binary content mode.
+///
+/// * `headers` - Headers of the HTTP request.
+/// * `name` - Name of the attribute without prefix.
+fn required_attribute(headers: &HeaderMap, name: &str) -> Result<String, EventError> {
+    attribute(headers, name).ok_or_else(|| {
+        let message = format!(
+            "Required CloudEvent header `{}{}` is missing.",
+            BINARY_MODE_HEADER_PREFIX, name
+        );
+        EventError::Invalid(message)
+    })
+}
+
+/// Checks if a content type describes JSON data, e.g. `application/json` or `application/cloudevents+json`.
+///
+/// * `content_type` - Content type to check.
+fn is_json_content_type(content_type: &str) -> bool {
+    let media_type = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    media_type == "application/json" || media_type.ends_with("+json")
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::HeaderValue};
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    fn structured_request(envelope: Value) -> Request {
+        Request::builder()
+            .header(CONTENT_TYPE, "application/cloudevents+json")
+            .body(Body::from(envelope.to_string()))
+            .unwrap()
+    }
+
+    fn binary_request(attributes: &[(&str, &str)], data: Value) -> Request {
+        let mut builder = Request::builder().header(CONTENT_TYPE, "application/json");
+        for (name, value) in attributes {
+            builder = builder.header(format!("{}{}", BINARY_MODE_HEADER_PREFIX, name), *value);
+        }
+        builder.body(Body::from(data.to_string())).unwrap()
+    }
+
+    fn binary_attributes() -> Vec<(&'static str, &'static str)> {
+        vec![
+            ("id", "event-1"),
+            ("source", "user"),
+            ("type", "com.dapr.event.sent"),
+            ("specversion", "1.0"),
+            ("topic", "user/user/created"),
+        ]
+    }
+
+    fn expect_invalid(event: Result<Event<Value>, EventError>) -> String {
+        match event {
+            Err(EventError::Invalid(message)) => message,
+            Err(EventError::Transient(message)) => {
+                panic!("Unexpected transient error: {}", message)
+            }
+            Ok(event) => panic!("Unexpected event: {:?}", event),
+        }
+    }
+
+    async fn receive(request: Request) -> Result<Event<Value>, EventError> {
+        let ReceivedEvent(event) = ReceivedEvent::from_request(request, &()).await.unwrap();
+        event
+    }
+
+    #[tokio::test]
+    async fn parses_structured_event() {
+        let request = structured_request(json!({
+            "id": "event-1",
+            "source": "user",
+            "type": "com.dapr.event.sent",
+            "specversion": "1.0",
+            "time": "2024-05-01T12:00:00Z",
+            "datacontenttype": "application/json",
+            "topic": "user/user/created",
+            "pubsubname": "pubsub",
+            "data": {"id": "user-1"},
+            "traceparent": "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+        }));
+        let event = receive(request).await.unwrap();
+        assert_eq!(event.id, "event-1");
+        assert_eq!(event.topic, "user/user/created");
+        assert_eq!(event.pubsubname.as_deref(), Some("pubsub"));
+        assert_eq!(event.data, json!({"id": "user-1"}));
+        assert!(event.traceparent.is_some());
+        assert_eq!(event.tracestate, None);
+    }
+
+    #[tokio::test]
+    async fn parses_binary_event() {
+        let mut attributes = binary_attributes();
+        attributes.push(("time", "2024-05-01T12:00:00Z"));
+        attributes.push(("tracestate", "vendor=value"));
+        let event = receive(binary_request(&attributes, json!({"id": "user-1"})))
+            .await
+            .unwrap();
+        assert_eq!(event.id, "event-1");
+        assert_eq!(event.source, "user");
+        assert_eq!(event.event_type, "com.dapr.event.sent");
+        assert_eq!(event.topic, "user/user/created");
+        assert_eq!(event.time.as_deref(), Some("2024-05-01T12:00:00Z"));
+        assert_eq!(event.datacontenttype.as_deref(), Some("application/json"));
+        assert_eq!(event.tracestate.as_deref(), Some("vendor=value"));
+        assert_eq!(event.pubsubname, None);
+        assert_eq!(event.data, json!({"id": "user-1"}));
+    }
+
+    #[tokio::test]
+    async fn rejects_binary_event_without_required_attribute() {
+        let attributes: Vec<_> = binary_attributes()
+            .into_iter()
+            .filter(|(name, _)| *name != "topic")
+            .collect();
+        let message = expect_invalid(receive(binary_request(&attributes, json!({}))).await);
+        assert_eq!(message, "Required CloudEvent header `ce-topic` is missing.");
+    }
+
+    #[tokio::test]
+    async fn rejects_malformed_structured_envelope() {
+        let request = Request::builder()
+            .body(Body::from("{\"id\": \"event-1\""))
+            .unwrap();
+        let message = expect_invalid(receive(request).await);
+        assert!(message.starts_with("CloudEvent envelope could not be parsed:"));
+    }
+
+    #[tokio::test]
+    async fn rejects_malformed_binary_data() {
+        let request = Request::builder()
+            .header("ce-specversion", HeaderValue::from_static("1.0"))
+            .body(Body::from("not json"))
+            .unwrap();
+        let message = expect_invalid(receive(request).await);
+        assert!(message.starts_with("CloudEvent data could not be parsed:"));
+    }
+
+    #[tokio::test]
+    async fn rejects_unsupported_spec_version() {
+        let mut attributes = binary_attributes();
+        attributes.retain(|(name, _)| *name != "specversion");
+        attributes.push(("specversion", "0.3"));
+        let message = expect_invalid(receive(binary_request(&attributes, json!({}))).await);
+        assert_eq!(
+            message,
+            "CloudEvent spec version `0.3` is not supported, expected `1.0`."
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_empty_attribute_and_invalid_time() {
+        let mut envelope = json!({
+            "id": "",
+            "source": "user",
+            "type": "com.dapr.event.sent",
+            "specversion": "1.0",
+            "topic": "user/user/created",
+            "data": {},
+        });
+        let message = expect_invalid(receive(structured_request(envelope.clone())).await);
+        assert_eq!(message, "CloudEvent attribute `id` must not be empty.");
+
+        envelope["id"] = json!("event-1");
+        envelope["time"] = json!("yesterday");
+        let message = expect_invalid(receive(structured_request(envelope)).await);
+        assert_eq!(
+            message,
+            "CloudEvent attribute `time`: `yesterday` is not a valid RFC 3339 timestamp."
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_non_json_data_content_type() {
+        let request = Request::builder()
+            .header(CONTENT_TYPE, "text/plain")
+            .header("ce-id", "event-1")
+            .header("ce-source", "user")
+            .header("ce-type", "com.dapr.event.sent")
+            .header("ce-specversion", "1.0")
+            .header("ce-topic", "user/user/created")
+            .body(Body::from("{}"))
+            .unwrap();
+        let message = expect_invalid(receive(request).await);
+        assert_eq!(
+            message,
+            "CloudEvent data content type `text/plain` is not supported, expected JSON."
+        );
+    }
+
+    #[test]
+    fn recognizes_json_content_types() {
+        assert!(is_json_content_type("application/json"));
+        assert!(is_json_content_type("Application/JSON; charset=utf-8"));
+        assert!(is_json_content_type("application/cloudevents+json"));
+        assert!(!is_json_content_type("text/plain"));
+        assert!(!is_json_content_type(""));
+    }
+}
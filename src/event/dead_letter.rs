@@ -0,0 +1,161 @@
+use bson::{doc, Bson, DateTime};
+use mongodb::{options::UpdateOptions, Collection};
+use serde_json::Value;
+
+use super::{
+    event_error::EventError, http_event_service::HttpEventServiceState,
+    processed_event::mark_as_processed, topic_handlers::TOPIC_REGISTRY,
+};
+use crate::graphql::model::failed_event::FailedEvent;
+
+/// Topic which Dapr publishes events to, that could not be delivered successfully.
+pub const DEAD_LETTER_TOPIC: &str = "review/dead-letter";
+
+/// Stores an event which failed to be processed or updates its stored error details.
+///
+/// * `collection` - MongoDB collection of failed events.
+/// * `id` - Id of the CloudEvent.
+/// * `topic` - Topic the event was published on.
+/// * `data` - Data of the event.
+/// * `error` - Error which occurred while processing the event.
+pub async fn record_failed_event(
+    collection: &Collection<FailedEvent>,
+    id: &str,
+    topic: &str,
+    data: &Value,
+    error: &EventError,
+) -> Result<(), EventError> {
+    let current_timestamp = DateTime::now();
+    let update = doc! {
+        "$set": {
+            "topic": topic,
+            "data": to_bson(data)?,
+            "error": error.to_string(),
+            "retryable": matches!(error, EventError::Transient(_)),
+            "replayable": true,
+            "last_failed_at": current_timestamp,
+        },
+        "$setOnInsert": {
+            "dead_lettered": false,
+            "first_failed_at": current_timestamp,
+        },
+        "$inc": {"attempts": 1},
+    };
+    upsert_failed_event(collection, id, update).await
+}
+
+/// Stores an event received on the dead-letter topic.
+///
+/// Topic, data and error details of an already recorded failure are kept, since Dapr replaces the topic
+/// of the event with the dead-letter topic.
+/// Events without a recorded failure are stored as not replayable, as their original topic is unknown.
+///
+/// * `collection` - MongoDB collection of failed events.
+/// * `id` - Id of the CloudEvent.
+/// * `topic` - Topic the dead-lettered event was received on.
+/// * `data` - Data of the event.
+pub async fn record_dead_lettered_event(
+    collection: &Collection<FailedEvent>,
+    id: &str,
+    topic: &str,
+    data: &Value,
+) -> Result<(), EventError> {
+    let current_timestamp = DateTime::now();
+    let update = doc! {
+        "$set": {
+            "dead_lettered": true,
+            "last_failed_at": current_timestamp,
+        },
+        "$setOnInsert": {
+            "topic": topic,
+            "data": to_bson(data)?,
+            "error": "Event was sent to the dead-letter topic by Dapr.",
+            "retryable": false,
+            "replayable": false,
+            "attempts": 0,
+            "first_failed_at": current_timestamp,
+        },
+    };
+    upsert_failed_event(collection, id, update).await
+}
+
+/// Removes the stored failure of an event, after it has been processed successfully.
+///
+/// * `collection` - MongoDB collection of failed events.
+/// * `id` - Id of the CloudEvent.
+pub async fn remove_failed_event(
+    collection: &Collection<FailedEvent>,
+    id: &str,
+) -> Result<(), EventError> {
+    collection.delete_one(doc! {"_id": id }, None).await?;
+    Ok(())
+}
+
+/// Replays a failed event through the normal event handler.
+///
+/// Removes the failed event if the replay succeeds, otherwise updates its error details.
+/// Fails without changes if the original topic of the event is unknown.
+///
+/// * `state` - Service state containing database connections.
+/// * `failed_event` - Failed event to replay.
+pub async fn replay_failed_event(
+    state: &HttpEventServiceState,
+    failed_event: FailedEvent,
+) -> Result<(), EventError> {
+    if !failed_event.replayable {
+        return Err(EventError::Invalid(
+            "Original topic of the dead-lettered event is unknown.".to_string(),
+        ));
+    }
+    let data = failed_event.data.into_relaxed_extjson();
+    match TOPIC_REGISTRY
+        .handle(state.clone(), &failed_event.topic, data.clone())
+        .await
+    {
+        Ok(()) => {
+            mark_as_processed(
+                &state.processed_event_collection,
+                &failed_event._id,
+                &failed_event.topic,
+            )
+            .await?;
+            remove_failed_event(&state.failed_event_collection, &failed_event._id).await
+        }
+        Err(error) => {
+            record_failed_event(
+                &state.failed_event_collection,
+                &failed_event._id,
+                &failed_event.topic,
+                &data,
+                &error,
+            )
+            .await?;
+            Err(error)
+        }
+    }
+}
+
+/// Upserts the failed event of an id with an update document.
+///
+/// * `collection` - MongoDB collection of failed events.
+/// * `id` - Id of the CloudEvent.
+/// * `update` - Update document to apply.
+async fn upsert_failed_event(
+    collection: &Collection<FailedEvent>,
+    id: &str,
+    update: bson::Document,
+) -> Result<(), EventError> {
+    let options = UpdateOptions::builder().upsert(true).build();
+    collection
+        .update_one(doc! {"_id": id }, update, options)
+        .await?;
+    Ok(())
+}
+
+/// Converts event data to BSON.
+///
+/// * `data` - Data of the event.
+fn to_bson(data: &Value) -> Result<Bson, EventError> {
+    bson::to_bson(data)
+        .map_err(|error| EventError::Invalid(format!("Event data could not be stored: {}", error)))
+}
@@ -0,0 +1,68 @@
+use std::collections::HashSet;
+
+use bson::{doc, DateTime, Uuid};
+use futures::TryStreamExt;
+use mongodb::Collection;
+use serde::{Deserialize, Serialize};
+
+use super::event_error::{is_duplicate_key_error, EventError};
+
+/// Tombstone of a user whose data has been erased.
+///
+/// Prevents late or replayed user created events from mirroring the user again.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ErasedUser {
+    /// UUID of the erased user.
+    pub _id: Uuid,
+    /// Timestamp when the data of the user was erased.
+    pub erased_at: DateTime,
+}
+
+/// Checks if the data of a user has been erased.
+///
+/// * `collection` - MongoDB collection of erased users.
+/// * `id` - UUID of the user.
+pub async fn is_erased(collection: &Collection<ErasedUser>, id: Uuid) -> Result<bool, EventError> {
+    let maybe_erased_user = collection.find_one(doc! {"_id": id }, None).await?;
+    Ok(maybe_erased_user.is_some())
+}
+
+/// Returns the UUIDs of the users whose data has been erased.
+///
+/// * `collection` - MongoDB collection of erased users.
+/// * `ids` - UUIDs of the users to check.
+pub async fn find_erased(
+    collection: &Collection<ErasedUser>,
+    ids: &[Uuid],
+) -> Result<HashSet<Uuid>, EventError> {
+    let erased_users: Vec<ErasedUser> = collection
+        .find(doc! {"_id": {"$in": ids}}, None)
+        .await?
+        .try_collect()
+        .await?;
+    Ok(erased_users
+        .into_iter()
+        .map(|erased_user| erased_user._id)
+        .collect())
+}
+
+/// Records the tombstone of an erased user.
+///
+/// Repeated erasures of the same user keep the first tombstone.
+///
+/// * `collection` - MongoDB collection of erased users.
+/// * `id` - UUID of the erased user.
+pub async fn record_erased_user(
+    collection: &Collection<ErasedUser>,
+    id: Uuid,
+) -> Result<(), EventError> {
+    let erased_user = ErasedUser {
+        _id: id,
+        erased_at: DateTime::now(),
+    };
+    match collection.insert_one(erased_user, None).await {
+        Ok(_) => Ok(()),
+        Err(error) if is_duplicate_key_error(&error) => Ok(()),
+        Err(error) => Err(error.into()),
+    }
+}
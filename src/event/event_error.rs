@@ -0,0 +1,71 @@
+use std::fmt;
+
+use mongodb::error::{ErrorKind, WriteFailure};
+
+use super::http_event_service::TopicEventResponseStatus;
+
+/// MongoDB error code of a duplicate key error.
+pub const DUPLICATE_KEY_ERROR_CODE: i32 = 11000;
+
+/// MongoDB error code of a failed document validation.
+const DOCUMENT_VALIDATION_FAILURE_CODE: i32 = 121;
+
+/// Error while handling a Dapr event, classified by whether a redelivery can succeed.
+#[derive(Debug)]
+pub enum EventError {
+    /// The event can never be processed, e.g. it has an unknown topic or invalid data.
+    Invalid(String),
+    /// Processing failed temporarily, e.g. MongoDB is not reachable.
+    Transient(String),
+}
+
+impl EventError {
+    /// Returns the Dapr status which tells Dapr to drop or redeliver the event.
+    pub fn status(&self) -> TopicEventResponseStatus {
+        match self {
+            Self::Invalid(_) => TopicEventResponseStatus::Drop,
+            Self::Transient(_) => TopicEventResponseStatus::Retry,
+        }
+    }
+}
+
+impl fmt::Display for EventError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Invalid(message) => write!(f, "Invalid event: {}", message),
+            Self::Transient(message) => write!(f, "Transient failure: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for EventError {}
+
+/// Classification of MongoDB errors.
+///
+/// Errors caused by the written document itself are invalid, all others are considered transient.
+impl From<mongodb::error::Error> for EventError {
+    fn from(error: mongodb::error::Error) -> Self {
+        match error.kind.as_ref() {
+            ErrorKind::Write(WriteFailure::WriteError(write_error))
+                if write_error.code == DOCUMENT_VALIDATION_FAILURE_CODE =>
+            {
+                Self::Invalid(error.to_string())
+            }
+            ErrorKind::BsonSerialization(_) | ErrorKind::InvalidArgument { .. } => {
+                Self::Invalid(error.to_string())
+            }
+            _ => Self::Transient(error.to_string()),
+        }
+    }
+}
+
+/// Checks if a MongoDB error is caused by a duplicate key.
+///
+/// * `error` - MongoDB error to check.
+pub fn is_duplicate_key_error(error: &mongodb::error::Error) -> bool {
+    matches!(
+        error.kind.as_ref(),
+        ErrorKind::Write(WriteFailure::WriteError(write_error))
+            if write_error.code == DUPLICATE_KEY_ERROR_CODE
+    )
+}
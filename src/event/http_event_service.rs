@@ -1,38 +1,80 @@
-use axum::{debug_handler, extract::State, http::StatusCode, Json};
-use bson::Uuid;
-use log::info;
-use mongodb::Collection;
+use axum::{
+    debug_handler,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use bson::{doc, Bson, Uuid};
+use log::{info, warn};
+use mongodb::{
+    options::{ReplaceOptions, UpdateOptions},
+    Collection, Database,
+};
+use opentelemetry::{
+    trace::{FutureExt, Status, TraceContextExt},
+    Context, KeyValue,
+};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-use crate::graphql::model::{product::Product, product_variant::ProductVariant, user::User};
-
-/// Data to send to Dapr in order to describe a subscription.
-#[derive(Serialize)]
-pub struct Pubsub {
-    #[serde(rename(serialize = "pubsubName"))]
-    pub pubsubname: String,
-    pub topic: String,
-    pub route: String,
-}
+use super::{
+    cloud_event::{Event, ReceivedEvent},
+    dead_letter::{record_dead_lettered_event, record_failed_event, remove_failed_event},
+    erased_user::{is_erased, record_erased_user, ErasedUser},
+    event_error::{is_duplicate_key_error, EventError},
+    processed_event::{is_processed, mark_as_processed, ProcessedEvent},
+    topic_handlers::TOPIC_REGISTRY,
+    topic_registry::{Pubsub, RegisteredHandler},
+};
+use crate::{
+    graphql::{
+        model::{
+            failed_event::FailedEvent,
+            product::Product,
+            product_variant::ProductVariant,
+            review::Review,
+            review_vote::{delete_votes_on_reviews, retract_votes_of_user, ReviewVote},
+            user::User,
+        },
+        response_cache::RESPONSE_CACHE,
+    },
+    telemetry::metrics::METRICS,
+};
 
 /// Reponse data to send to Dapr when receiving an event.
-#[derive(Serialize)]
+#[derive(Serialize, Default)]
 pub struct TopicEventResponse {
-    pub status: u8,
+    pub status: TopicEventResponseStatus,
 }
 
-/// Default status is `0` -> Ok, according to Dapr specs.
-impl Default for TopicEventResponse {
-    fn default() -> Self {
-        Self { status: 0 }
+/// Status of a received event, according to Dapr specs.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum TopicEventResponseStatus {
+    /// Event was processed successfully.
+    #[default]
+    Success,
+    /// Dapr should redeliver the event.
+    Retry,
+    /// Dapr should drop the event.
+    Drop,
+}
+
+impl TopicEventResponseStatus {
+    /// Returns the status as lowercase label for metrics.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Success => "success",
+            Self::Retry => "retry",
+            Self::Drop => "drop",
+        }
     }
 }
 
-/// Relevant part of Dapr event wrapped in a cloud envelope.
-#[derive(Deserialize, Debug)]
-pub struct Event<T> {
-    pub topic: String,
-    pub data: T,
+impl From<TopicEventResponseStatus> for TopicEventResponse {
+    fn from(status: TopicEventResponseStatus) -> Self {
+        Self { status }
+    }
 }
 
 /// Relevant part of Dapr event data.
@@ -43,12 +85,20 @@ pub struct EventData {
 
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
-/// Relevant part of product variant creation event data.
+/// Relevant part of product variant creation and update event data.
 pub struct ProductVariantEventData {
     /// Product variant UUID.
     pub id: Uuid,
     /// Associated product UUID.
     pub product_id: Uuid,
+    /// Name of the product variant.
+    pub name: Option<String>,
+    /// Stock keeping unit of the product variant.
+    pub sku: Option<String>,
+    /// Flag if product variant is active.
+    pub is_active: Option<bool>,
+    /// Category of the associated product.
+    pub product_category: Option<String>,
 }
 
 /// Service state containing database connections.
@@ -57,100 +107,373 @@ pub struct HttpEventServiceState {
     pub product_collection: Collection<Product>,
     pub product_variant_collection: Collection<ProductVariant>,
     pub user_collection: Collection<User>,
+    pub processed_event_collection: Collection<ProcessedEvent>,
+    pub erased_user_collection: Collection<ErasedUser>,
+    pub failed_event_collection: Collection<FailedEvent>,
+    pub review_collection: Collection<Review>,
+    pub review_vote_collection: Collection<ReviewVote>,
+}
+
+impl HttpEventServiceState {
+    /// Creates the service state from a database client.
+    ///
+    /// * `db_client` - MongoDB database client.
+    pub fn new(db_client: &Database) -> Self {
+        Self {
+            product_collection: db_client.collection::<Product>("products"),
+            product_variant_collection: db_client.collection::<ProductVariant>("product_variants"),
+            user_collection: db_client.collection::<User>("users"),
+            processed_event_collection: db_client.collection::<ProcessedEvent>("processed_events"),
+            erased_user_collection: db_client.collection::<ErasedUser>("erased_users"),
+            failed_event_collection: db_client.collection::<FailedEvent>("failed_events"),
+            review_collection: db_client.collection::<Review>("reviews"),
+            review_vote_collection: db_client.collection::<ReviewVote>("review_votes"),
+        }
+    }
 }
 
 /// HTTP endpoint to list topic subsciptions.
 pub async fn list_topic_subscriptions() -> Result<Json<Vec<Pubsub>>, StatusCode> {
-    let pubsub_user = Pubsub {
-        pubsubname: "pubsub".to_string(),
-        topic: "user/user/created".to_string(),
-        route: "/on-topic-event".to_string(),
-    };
-    let pubsub_product = Pubsub {
-        pubsubname: "pubsub".to_string(),
-        topic: "catalog/product/created".to_string(),
-        route: "/on-topic-event".to_string(),
-    };
-    let pubsub_product_variant = Pubsub {
-        pubsubname: "pubsub".to_string(),
-        topic: "catalog/product-variant/created".to_string(),
-        route: "/on-topic-event".to_string(),
-    };
-    Ok(Json(vec![
-        pubsub_user,
-        pubsub_product,
-        pubsub_product_variant,
-    ]))
+    Ok(Json(TOPIC_REGISTRY.subscriptions()))
 }
 
-/// HTTP endpoint to receive events.
+/// Receives events on the route of a topic handler.
 ///
 /// * `state` - Service state containing database connections.
-/// * `event` - Event handled by endpoint.
-#[debug_handler(state = HttpEventServiceState)]
+/// * `headers` - Header map of the request, used for trace context propagation.
+/// * `received_event` - Event handled by endpoint.
+/// * `handler` - Topic handler registered for the route.
 pub async fn on_topic_event(
-    State(state): State<HttpEventServiceState>,
-    Json(event): Json<Event<EventData>>,
-) -> Result<Json<TopicEventResponse>, StatusCode> {
-    info!("{:?}", event);
-
-    match event.topic.as_str() {
-        "user/user/created" => create_in_mongodb(&state.user_collection, event.data.id).await?,
-        "catalog/product/created" => {
-            create_in_mongodb(&state.product_collection, event.data.id).await?
-        }
-        _ => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+    state: HttpEventServiceState,
+    headers: HeaderMap,
+    received_event: Result<Event<Value>, EventError>,
+    handler: &RegisteredHandler,
+) -> Json<TopicEventResponse> {
+    let event = match received_event {
+        Ok(event) => event,
+        Err(error) => return reject_event(error),
+    };
+    if event.topic != handler.topic {
+        let message = format!(
+            "Topic `{}` is not handled on route `{}`.",
+            event.topic, handler.route
+        );
+        return reject_event(EventError::Invalid(message));
     }
-    Ok(Json(TopicEventResponse::default()))
+    process_event(&state, &headers, &event, handler).await
 }
 
-/// HTTP endpoint to product variant creation events.
+/// HTTP endpoint to receive events from the dead-letter topic.
+///
+/// Stores the events in the failed events collection, where they can be inspected and replayed.
 ///
 /// * `state` - Service state containing database connections.
+/// * `headers` - Header map of the request, used for trace context propagation.
 /// * `event` - Event handled by endpoint.
 #[debug_handler(state = HttpEventServiceState)]
-pub async fn on_product_variant_creation_event(
+pub async fn on_dead_letter_event(
     State(state): State<HttpEventServiceState>,
-    Json(event): Json<Event<ProductVariantEventData>>,
-) -> Result<Json<TopicEventResponse>, StatusCode> {
-    info!("{:?}", event);
-
-    match event.topic.as_str() {
-        "catalog/product-variant/created" => {
-            let product_variant = ProductVariant::from(event.data);
-            add_product_variant_to_mongodb(&state.product_variant_collection, product_variant)
-                .await?
+    headers: HeaderMap,
+    ReceivedEvent(received_event): ReceivedEvent<Value>,
+) -> Json<TopicEventResponse> {
+    let event = match received_event {
+        Ok(event) => event,
+        Err(error) => return reject_event(error),
+    };
+    let log_context = event.log_context(&headers);
+    log_context
+        .scope(async {
+            event.log_envelope();
+
+            let span_context = event.start_span(&headers);
+            let result = record_dead_lettered_event(
+                &state.failed_event_collection,
+                &event.id,
+                &event.topic,
+                &event.data,
+            )
+            .with_context(span_context.clone())
+            .await;
+            let status = record_event_result(&span_context, &event.topic, result);
+            Json(TopicEventResponse::from(status))
+        })
+        .await
+}
+
+/// Answers an event which could not be parsed or is invalid.
+///
+/// * `error` - Error describing why the event is invalid.
+fn reject_event(error: EventError) -> Json<TopicEventResponse> {
+    warn!("Rejecting event: {}", error);
+    let status = error.status();
+    METRICS.events_handled.add(
+        1,
+        &[
+            KeyValue::new("topic", "unknown"),
+            KeyValue::new("outcome", status.as_str()),
+        ],
+    );
+    Json(TopicEventResponse::from(status))
+}
+
+/// Processes an event exactly once per CloudEvent id and returns the Dapr response.
+///
+/// Skips events which have already been processed, handles the event in the log context and span of the event
+/// and records its id after successful processing.
+/// Failures are stored in the failed events collection until the event is processed successfully.
+///
+/// * `state` - Service state containing database connections.
+/// * `headers` - Header map of the request delivering the event.
+/// * `event` - Event to process.
+/// * `handler` - Topic handler of the event.
+async fn process_event(
+    state: &HttpEventServiceState,
+    headers: &HeaderMap,
+    event: &Event<Value>,
+    handler: &RegisteredHandler,
+) -> Json<TopicEventResponse> {
+    let log_context = event.log_context(headers);
+    log_context
+        .scope(async {
+            event.log_envelope();
+
+            let span_context = event.start_span(headers);
+            let result = async {
+                if is_processed(&state.processed_event_collection, &event.id).await? {
+                    info!("Event `{}` has already been processed.", event.id);
+                    return Ok(());
+                }
+                if let Err(error) = handler.handle(state.clone(), event.data.clone()).await {
+                    if let Err(record_error) = record_failed_event(
+                        &state.failed_event_collection,
+                        &event.id,
+                        &event.topic,
+                        &event.data,
+                        &error,
+                    )
+                    .await
+                    {
+                        warn!(
+                            "Recording failed event `{}` failed: {}",
+                            event.id, record_error
+                        );
+                    }
+                    return Err(error);
+                }
+                mark_as_processed(&state.processed_event_collection, &event.id, &event.topic)
+                    .await?;
+                remove_failed_event(&state.failed_event_collection, &event.id).await
+            }
+            .with_context(span_context.clone())
+            .await;
+            let status = record_event_result(&span_context, &event.topic, result);
+            Json(TopicEventResponse::from(status))
+        })
+        .await
+}
+
+/// Records the result of an event handler on its span and in the event metrics and returns the Dapr status.
+///
+/// * `span_context` - Context containing the span of the event.
+/// * `topic` - Topic of the handled event.
+/// * `result` - Result of the event handler.
+fn record_event_result(
+    span_context: &Context,
+    topic: &str,
+    result: Result<(), EventError>,
+) -> TopicEventResponseStatus {
+    let span = span_context.span();
+    let status = match result {
+        Ok(()) => {
+            span.set_status(Status::Ok);
+            TopicEventResponseStatus::Success
         }
-        _ => return Err(StatusCode::INTERNAL_SERVER_ERROR),
-    }
-    Ok(Json(TopicEventResponse::default()))
+        Err(error) => {
+            warn!("Handling event of topic `{}` failed: {}", topic, error);
+            span.set_status(Status::error(error.to_string()));
+            error.status()
+        }
+    };
+    METRICS.events_handled.add(
+        1,
+        &[
+            KeyValue::new("topic", topic.to_string()),
+            KeyValue::new("outcome", status.as_str()),
+        ],
+    );
+    span.end();
+    status
 }
 
 /// Add a newly created product variant to MongoDB.
 ///
+/// Only inserts the product variant if it does not exist yet, so that redelivered events do not fail
+/// and a late created event does not overwrite metadata of a newer update.
+///
 /// * `collection` - MongoDB collection to add newly created product variant to.
 /// * `product_variant` - Newly created product variant.
 pub async fn add_product_variant_to_mongodb(
     collection: &Collection<ProductVariant>,
     product_variant: ProductVariant,
-) -> Result<(), StatusCode> {
-    match collection.insert_one(product_variant, None).await {
-        Ok(_) => Ok(()),
-        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+) -> Result<(), EventError> {
+    insert_if_absent_in_mongodb(collection, product_variant._id, &product_variant).await
+}
+
+/// Updates the metadata of a product variant in MongoDB.
+///
+/// Upserts the product variant and refreshes the copies embedded in its reviews.
+/// Invalidates all cached review reads, since they may contain the product variant.
+///
+/// * `state` - Service state containing database connections.
+/// * `product_variant` - Updated product variant.
+pub async fn update_product_variant_in_mongodb(
+    state: &HttpEventServiceState,
+    product_variant: ProductVariant,
+) -> Result<(), EventError> {
+    upsert_in_mongodb(
+        &state.product_variant_collection,
+        product_variant._id,
+        &product_variant,
+    )
+    .await?;
+    state
+        .review_collection
+        .update_many(
+            doc! {"product_variant._id": product_variant._id },
+            doc! {"$set": {"product_variant": Bson::from(product_variant)}},
+            None,
+        )
+        .await?;
+    RESPONSE_CACHE.invalidate_all().await;
+    Ok(())
+}
+
+/// Erases all data tied to a user in MongoDB.
+///
+/// Deletes the reviews of the user and the votes on them, retracts the votes of the user,
+/// and deletes the mirrored user and failed events carrying the UUID of the user.
+/// Records a tombstone of the user first, so that the user is not mirrored again by later user created events.
+/// Invalidates all cached review reads, since they may contain reviews of the user.
+/// Returns the number of deleted reviews.
+///
+/// * `state` - Service state containing database connections.
+/// * `user_id` - UUID of the user to erase.
+pub async fn erase_user_data(
+    state: &HttpEventServiceState,
+    user_id: Uuid,
+) -> Result<u64, EventError> {
+    record_erased_user(&state.erased_user_collection, user_id).await?;
+    retract_votes_of_user(
+        &state.review_vote_collection,
+        &state.review_collection,
+        user_id,
+    )
+    .await?;
+    let review_ids: Vec<Uuid> = state
+        .review_collection
+        .distinct("_id", doc! {"user._id": user_id }, None)
+        .await?
+        .into_iter()
+        .filter_map(|id| match id {
+            Bson::Binary(binary) => binary.to_uuid().ok(),
+            _ => None,
+        })
+        .collect();
+    delete_votes_on_reviews(&state.review_vote_collection, review_ids).await?;
+    let delete_result = state
+        .review_collection
+        .delete_many(doc! {"user._id": user_id }, None)
+        .await?;
+    state
+        .user_collection
+        .delete_one(doc! {"_id": user_id }, None)
+        .await?;
+    state
+        .failed_event_collection
+        .delete_many(doc! {"data.id": user_id.to_string() }, None)
+        .await?;
+    RESPONSE_CACHE.invalidate_all().await;
+    Ok(delete_result.deleted_count)
+}
+
+/// Adds a newly created user to MongoDB, unless the data of the user has been erased.
+///
+/// * `state` - Service state containing database connections.
+/// * `user_id` - UUID of the newly created user.
+pub async fn create_user_in_mongodb(
+    state: &HttpEventServiceState,
+    user_id: Uuid,
+) -> Result<(), EventError> {
+    if is_erased(&state.erased_user_collection, user_id).await? {
+        info!(
+            "User with the UUID: `{}` has been erased and is not added again.",
+            user_id
+        );
+        return Ok(());
     }
+    create_in_mongodb(&state.user_collection, user_id).await
 }
 
 /// Create a new object: `T` in MongoDB.
 ///
+/// Only inserts the object if it does not exist yet, so that redelivered events do not fail.
+///
 /// * `collection` - MongoDB collection to add newly created object to.
 /// * `id` - UUID of newly created object.
 pub async fn create_in_mongodb<T: Serialize + From<Uuid>>(
     collection: &Collection<T>,
     id: Uuid,
-) -> Result<(), StatusCode> {
+) -> Result<(), EventError> {
     let object = T::from(id);
-    match collection.insert_one(object, None).await {
+    insert_if_absent_in_mongodb(collection, id, &object).await
+}
+
+/// Inserts an object: `T` of an UUID in MongoDB if it does not exist yet.
+///
+/// An existing object is left unchanged.
+/// A duplicate key error caused by a concurrent insert of the same object is treated as success.
+///
+/// * `collection` - MongoDB collection to insert object in.
+/// * `id` - UUID of object.
+/// * `object` - Object to insert.
+async fn insert_if_absent_in_mongodb<T: Serialize>(
+    collection: &Collection<T>,
+    id: Uuid,
+    object: &T,
+) -> Result<(), EventError> {
+    let mut document =
+        bson::to_document(object).map_err(|error| EventError::Invalid(error.to_string()))?;
+    document.remove("_id");
+    let options = UpdateOptions::builder().upsert(true).build();
+    match collection
+        .update_one(doc! {"_id": id }, doc! {"$setOnInsert": document}, options)
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(error) if is_duplicate_key_error(&error) => Ok(()),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Inserts or replaces an object: `T` of an UUID in MongoDB.
+///
+/// A duplicate key error caused by a concurrent upsert of the same object is treated as success.
+///
+/// * `collection` - MongoDB collection to upsert object in.
+/// * `id` - UUID of object.
+/// * `object` - Object to upsert.
+async fn upsert_in_mongodb<T: Serialize>(
+    collection: &Collection<T>,
+    id: Uuid,
+    object: &T,
+) -> Result<(), EventError> {
+    let options = ReplaceOptions::builder().upsert(true).build();
+    match collection
+        .replace_one(doc! {"_id": id }, object, options)
+        .await
+    {
         Ok(_) => Ok(()),
-        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+        Err(error) if is_duplicate_key_error(&error) => Ok(()),
+        Err(error) => Err(error.into()),
     }
 }
@@ -1 +1,8 @@
+pub mod cloud_event;
+pub mod dead_letter;
+pub mod erased_user;
+pub mod event_error;
 pub mod http_event_service;
+pub mod processed_event;
+pub mod topic_handlers;
+pub mod topic_registry;
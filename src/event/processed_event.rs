@@ -0,0 +1,73 @@
+use std::time::Duration;
+
+use bson::{doc, DateTime};
+use mongodb::{options::IndexOptions, Collection, IndexModel};
+use serde::{Deserialize, Serialize};
+
+use super::event_error::{is_duplicate_key_error, EventError};
+
+/// Time after which processed event ids are removed, must exceed the maximum redelivery period of Dapr.
+const PROCESSED_EVENT_RETENTION: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Id of a CloudEvent which has already been processed successfully.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProcessedEvent {
+    /// Id of the CloudEvent.
+    pub _id: String,
+    /// Topic the event was received on.
+    pub topic: String,
+    /// Timestamp when the event was processed.
+    pub processed_at: DateTime,
+}
+
+/// Creates the TTL index which removes processed events after the retention period.
+///
+/// * `collection` - MongoDB collection of processed events.
+pub async fn create_processed_event_index(
+    collection: &Collection<ProcessedEvent>,
+) -> mongodb::error::Result<()> {
+    let index_options = IndexOptions::builder()
+        .expire_after(PROCESSED_EVENT_RETENTION)
+        .build();
+    let index = IndexModel::builder()
+        .keys(doc! {"processed_at": 1})
+        .options(index_options)
+        .build();
+    collection.create_index(index, None).await.map(|_| ())
+}
+
+/// Checks if the event of an id has already been processed.
+///
+/// * `collection` - MongoDB collection of processed events.
+/// * `id` - Id of the CloudEvent.
+pub async fn is_processed(
+    collection: &Collection<ProcessedEvent>,
+    id: &str,
+) -> Result<bool, EventError> {
+    let maybe_processed_event = collection.find_one(doc! {"_id": id }, None).await?;
+    Ok(maybe_processed_event.is_some())
+}
+
+/// Marks the event of an id as processed.
+///
+/// Concurrent deliveries of the same event may both be processed, in which case the duplicate key error is ignored.
+///
+/// * `collection` - MongoDB collection of processed events.
+/// * `id` - Id of the CloudEvent.
+/// * `topic` - Topic the event was received on.
+pub async fn mark_as_processed(
+    collection: &Collection<ProcessedEvent>,
+    id: &str,
+    topic: &str,
+) -> Result<(), EventError> {
+    let processed_event = ProcessedEvent {
+        _id: id.to_string(),
+        topic: topic.to_string(),
+        processed_at: DateTime::now(),
+    };
+    match collection.insert_one(processed_event, None).await {
+        Ok(_) => Ok(()),
+        Err(error) if is_duplicate_key_error(&error) => Ok(()),
+        Err(error) => Err(error.into()),
+    }
+}
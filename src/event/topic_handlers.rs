@@ -0,0 +1,165 @@
+use once_cell::sync::Lazy;
+
+use super::{
+    dead_letter::DEAD_LETTER_TOPIC,
+    event_error::EventError,
+    http_event_service::{
+        add_product_variant_to_mongodb, create_in_mongodb, create_user_in_mongodb, erase_user_data,
+        update_product_variant_in_mongodb, EventData, HttpEventServiceState,
+        ProductVariantEventData,
+    },
+    topic_registry::{TopicHandler, TopicRegistry, DEFAULT_PUBSUB_NAME},
+};
+use crate::graphql::model::product_variant::ProductVariant;
+
+/// Registry of all topics the service subscribes to.
+pub static TOPIC_REGISTRY: Lazy<TopicRegistry> = Lazy::new(|| {
+    TopicRegistry::default()
+        .register::<UserCreatedHandler>()
+        .register::<UserDeletedHandler>()
+        .register::<ProductCreatedHandler>()
+        .register::<ProductVariantCreatedHandler>()
+        .register::<ProductVariantUpdatedHandler>()
+        .with_dead_letter_topic(
+            DEFAULT_PUBSUB_NAME,
+            DEAD_LETTER_TOPIC,
+            "/on-dead-letter-event",
+        )
+});
+
+/// Adds newly created users to MongoDB, unless their data has been erased.
+pub struct UserCreatedHandler;
+
+impl TopicHandler for UserCreatedHandler {
+    type Data = EventData;
+
+    const TOPIC: &'static str = "user/user/created";
+    const ROUTE: &'static str = "/on-user-creation-event";
+
+    async fn handle(state: HttpEventServiceState, data: EventData) -> Result<(), EventError> {
+        create_user_in_mongodb(&state, data.id).await
+    }
+}
+
+/// Erases all data of deleted users from MongoDB.
+pub struct UserDeletedHandler;
+
+impl TopicHandler for UserDeletedHandler {
+    type Data = EventData;
+
+    const TOPIC: &'static str = "user/user/deleted";
+    const ROUTE: &'static str = "/on-user-deletion-event";
+
+    async fn handle(state: HttpEventServiceState, data: EventData) -> Result<(), EventError> {
+        erase_user_data(&state, data.id).await.map(|_| ())
+    }
+}
+
+/// Adds newly created products to MongoDB.
+pub struct ProductCreatedHandler;
+
+impl TopicHandler for ProductCreatedHandler {
+    type Data = EventData;
+
+    const TOPIC: &'static str = "catalog/product/created";
+    const ROUTE: &'static str = "/on-product-creation-event";
+
+    async fn handle(state: HttpEventServiceState, data: EventData) -> Result<(), EventError> {
+        create_in_mongodb(&state.product_collection, data.id).await
+    }
+}
+
+/// Adds newly created product variants to MongoDB.
+pub struct ProductVariantCreatedHandler;
+
+impl TopicHandler for ProductVariantCreatedHandler {
+    type Data = ProductVariantEventData;
+
+    const TOPIC: &'static str = "catalog/product-variant/created";
+    const ROUTE: &'static str = "/on-product-variant-creation-event";
+
+    async fn handle(
+        state: HttpEventServiceState,
+        data: ProductVariantEventData,
+    ) -> Result<(), EventError> {
+        let product_variant = ProductVariant::from(&data);
+        add_product_variant_to_mongodb(&state.product_variant_collection, product_variant).await
+    }
+}
+
+/// Updates the metadata of product variants in MongoDB.
+pub struct ProductVariantUpdatedHandler;
+
+impl TopicHandler for ProductVariantUpdatedHandler {
+    type Data = ProductVariantEventData;
+
+    const TOPIC: &'static str = "catalog/product-variant/updated";
+    const ROUTE: &'static str = "/on-product-variant-update-event";
+
+    async fn handle(
+        state: HttpEventServiceState,
+        data: ProductVariantEventData,
+    ) -> Result<(), EventError> {
+        let product_variant = ProductVariant::from(&data);
+        update_product_variant_in_mongodb(&state, product_variant).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    #[test]
+    fn subscribes_to_every_registered_topic() {
+        let subscriptions = serde_json::to_value(TOPIC_REGISTRY.subscriptions()).unwrap();
+        let subscription = |topic: &str, route: &str| {
+            json!({
+                "pubsubName": "pubsub",
+                "topic": topic,
+                "routes": {"rules": [], "default": route},
+                "deadLetterTopic": DEAD_LETTER_TOPIC,
+            })
+        };
+        assert_eq!(
+            subscriptions,
+            Value::Array(vec![
+                subscription("user/user/created", "/on-user-creation-event"),
+                subscription("user/user/deleted", "/on-user-deletion-event"),
+                subscription("catalog/product/created", "/on-product-creation-event"),
+                subscription(
+                    "catalog/product-variant/created",
+                    "/on-product-variant-creation-event"
+                ),
+                subscription(
+                    "catalog/product-variant/updated",
+                    "/on-product-variant-update-event"
+                ),
+                json!({
+                    "pubsubName": "pubsub",
+                    "topic": DEAD_LETTER_TOPIC,
+                    "routes": {"rules": [], "default": "/on-dead-letter-event"},
+                }),
+            ])
+        );
+    }
+
+    #[test]
+    fn finds_handler_of_every_registered_topic() {
+        for subscription in TOPIC_REGISTRY.subscriptions() {
+            if subscription.topic == DEAD_LETTER_TOPIC {
+                continue;
+            }
+            let handler = TOPIC_REGISTRY
+                .handler_for_topic(&subscription.topic)
+                .unwrap();
+            assert_eq!(subscription.routes.default.as_deref(), Some(handler.route));
+        }
+    }
+
+    #[test]
+    fn builds_router_of_registered_routes() {
+        let _router = TOPIC_REGISTRY.router();
+    }
+}
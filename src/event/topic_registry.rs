@@ -0,0 +1,412 @@
+use std::{future::Future, pin::Pin};
+
+use axum::{extract::State, http::HeaderMap, routing::post, Router};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use super::{
+    cloud_event::ReceivedEvent,
+    event_error::EventError,
+    http_event_service::{on_dead_letter_event, on_topic_event, HttpEventServiceState},
+};
+
+/// Name of the Dapr pub/sub component used by default.
+pub const DEFAULT_PUBSUB_NAME: &str = "pubsub";
+
+/// Future returned by a type-erased topic handler.
+pub type HandlerFuture = Pin<Box<dyn Future<Output = Result<(), EventError>> + Send>>;
+
+/// Handler of the events published on a topic.
+///
+/// Declares everything needed to subscribe to the topic via Dapr and to route the events to the handler.
+pub trait TopicHandler: 'static {
+    /// Type of the event data.
+    type Data: DeserializeOwned + Send;
+
+    /// Name of the Dapr pub/sub component the topic belongs to.
+    const PUBSUB_NAME: &'static str = DEFAULT_PUBSUB_NAME;
+    /// Topic to subscribe to.
+    const TOPIC: &'static str;
+    /// HTTP route Dapr delivers the events to, must be unique.
+    const ROUTE: &'static str;
+    /// Optional CEL expression, e.g. `event.type == "created"`.
+    ///
+    /// Handlers with a rule only receive matching events of the topic,
+    /// the handler without a rule receives all events which do not match any rule.
+    const RULE: Option<&'static str> = None;
+
+    /// Handles the data of an event.
+    ///
+    /// * `state` - Service state containing database connections.
+    /// * `data` - Data of the event.
+    fn handle(
+        state: HttpEventServiceState,
+        data: Self::Data,
+    ) -> impl Future<Output = Result<(), EventError>> + Send;
+}
+
+/// Type-erased topic handler stored in the registry.
+pub struct RegisteredHandler {
+    pub pubsub_name: &'static str,
+    pub topic: &'static str,
+    pub route: &'static str,
+    pub rule: Option<&'static str>,
+    handle: fn(HttpEventServiceState, Value) -> HandlerFuture,
+}
+
+impl RegisteredHandler {
+    /// Parses the event data into the payload type of the handler and handles it.
+    ///
+    /// * `state` - Service state containing database connections.
+    /// * `data` - Data of the event.
+    pub fn handle(&self, state: HttpEventServiceState, data: Value) -> HandlerFuture {
+        (self.handle)(state, data)
+    }
+}
+
+/// Parses the event data for a topic handler and calls it.
+///
+/// * `state` - Service state containing database connections.
+/// * `data` - Data of the event.
+fn handle_erased<H: TopicHandler>(state: HttpEventServiceState, data: Value) -> HandlerFuture {
+    Box::pin(async move {
+        let data = serde_json::from_value::<H::Data>(data).map_err(|error| {
+            EventError::Invalid(format!("Event data could not be parsed: {}", error))
+        })?;
+        H::handle(state, data).await
+    })
+}
+
+/// Dead-letter topic all subscriptions of the registry forward undeliverable events to.
+struct DeadLetterSubscription {
+    pubsub_name: &'static str,
+    topic: &'static str,
+    route: &'static str,
+}
+
+/// Registry of all topics the service subscribes to.
+///
+/// Generates the Dapr subscriptions and the HTTP routes of the event handlers.
+#[derive(Default)]
+pub struct TopicRegistry {
+    handlers: Vec<RegisteredHandler>,
+    dead_letter_subscription: Option<DeadLetterSubscription>,
+}
+
+impl TopicRegistry {
+    /// Registers a topic handler.
+    ///
+    /// Panics if the route of the handler is already registered.
+    pub fn register<H: TopicHandler>(mut self) -> Self {
+        self.assert_route_is_free(H::ROUTE);
+        self.handlers.push(RegisteredHandler {
+            pubsub_name: H::PUBSUB_NAME,
+            topic: H::TOPIC,
+            route: H::ROUTE,
+            rule: H::RULE,
+            handle: handle_erased::<H>,
+        });
+        self
+    }
+
+    /// Sets the dead-letter topic of all subscriptions and subscribes to it.
+    ///
+    /// Panics if the route is already registered.
+    ///
+    /// * `pubsub_name` - Name of the Dapr pub/sub component of the dead-letter topic.
+    /// * `topic` - Dead-letter topic.
+    /// * `route` - HTTP route Dapr delivers dead-lettered events to.
+    pub fn with_dead_letter_topic(
+        mut self,
+        pubsub_name: &'static str,
+        topic: &'static str,
+        route: &'static str,
+    ) -> Self {
+        self.assert_route_is_free(route);
+        self.dead_letter_subscription = Some(DeadLetterSubscription {
+            pubsub_name,
+            topic,
+            route,
+        });
+        self
+    }
+
+    /// Returns the Dapr subscriptions, one per pub/sub component and topic.
+    pub fn subscriptions(&self) -> Vec<Pubsub> {
+        let dead_letter_topic = self
+            .dead_letter_subscription
+            .as_ref()
+            .map(|dead_letter_subscription| dead_letter_subscription.topic.to_string());
+        let mut subscriptions: Vec<Pubsub> = Vec::new();
+        for handler in &self.handlers {
+            let subscription = match subscriptions.iter_mut().find(|subscription| {
+                subscription.pubsubname == handler.pubsub_name
+                    && subscription.topic == handler.topic
+            }) {
+                Some(subscription) => subscription,
+                None => {
+                    subscriptions.push(Pubsub {
+                        pubsubname: handler.pubsub_name.to_string(),
+                        topic: handler.topic.to_string(),
+                        routes: Routes::default(),
+                        dead_letter_topic: dead_letter_topic.clone(),
+                    });
+                    subscriptions.last_mut().unwrap()
+                }
+            };
+            match handler.rule {
+                Some(definitely_rule) => subscription.routes.rules.push(RoutingRule {
+                    match_expression: definitely_rule.to_string(),
+                    path: handler.route.to_string(),
+                }),
+                None => subscription.routes.default = Some(handler.route.to_string()),
+            }
+        }
+        if let Some(dead_letter_subscription) = &self.dead_letter_subscription {
+            subscriptions.push(Pubsub {
+                pubsubname: dead_letter_subscription.pubsub_name.to_string(),
+                topic: dead_letter_subscription.topic.to_string(),
+                routes: Routes {
+                    rules: Vec::new(),
+                    default: Some(dead_letter_subscription.route.to_string()),
+                },
+                dead_letter_topic: None,
+            });
+        }
+        subscriptions
+    }
+
+    /// Returns a router containing a route for every registered handler and the dead-letter topic.
+    pub fn router(&'static self) -> Router<HttpEventServiceState> {
+        let mut router = Router::new();
+        for handler in &self.handlers {
+            router = router.route(
+                handler.route,
+                post(
+                    move |State(state): State<HttpEventServiceState>,
+                          headers: HeaderMap,
+                          ReceivedEvent(received_event): ReceivedEvent<Value>| async move {
+                        on_topic_event(state, headers, received_event, handler).await
+                    },
+                ),
+            );
+        }
+        if let Some(dead_letter_subscription) = &self.dead_letter_subscription {
+            router = router.route(dead_letter_subscription.route, post(on_dead_letter_event));
+        }
+        router
+    }
+
+    /// Returns the handler of a topic, which receives events not matching any rule if there are several.
+    ///
+    /// * `topic` - Topic to find the handler for.
+    pub fn handler_for_topic(&self, topic: &str) -> Option<&RegisteredHandler> {
+        let mut topic_handlers = self
+            .handlers
+            .iter()
+            .filter(|handler| handler.topic == topic);
+        let first_handler = topic_handlers.next()?;
+        if first_handler.rule.is_none() {
+            return Some(first_handler);
+        }
+        topic_handlers
+            .find(|handler| handler.rule.is_none())
+            .or(Some(first_handler))
+    }
+
+    /// Handles the data of an event with the handler of its topic.
+    ///
+    /// * `state` - Service state containing database connections.
+    /// * `topic` - Topic the event was published on.
+    /// * `data` - Data of the event.
+    pub async fn handle(
+        &self,
+        state: HttpEventServiceState,
+        topic: &str,
+        data: Value,
+    ) -> Result<(), EventError> {
+        match self.handler_for_topic(topic) {
+            Some(handler) => handler.handle(state, data).await,
+            None => Err(EventError::Invalid(format!(
+                "Topic `{}` is not handled by this service.",
+                topic
+            ))),
+        }
+    }
+
+    /// Panics if a route is already registered, since Dapr could not distinguish the handlers.
+    ///
+    /// * `route` - Route to check.
+    fn assert_route_is_free(&self, route: &str) {
+        let route_is_taken = self.handlers.iter().any(|handler| handler.route == route)
+            || self
+                .dead_letter_subscription
+                .as_ref()
+                .is_some_and(|dead_letter_subscription| dead_letter_subscription.route == route);
+        if route_is_taken {
+            panic!(
+                "Route `{}` is registered for multiple topic handlers.",
+                route
+            );
+        }
+    }
+}
+
+/// Data to send to Dapr in order to describe a subscription.
+#[derive(Serialize)]
+pub struct Pubsub {
+    #[serde(rename(serialize = "pubsubName"))]
+    pub pubsubname: String,
+    pub topic: String,
+    /// Routes of the events of the topic.
+    pub routes: Routes,
+    /// Topic which Dapr sends events to, that could not be delivered successfully.
+    #[serde(
+        rename(serialize = "deadLetterTopic"),
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub dead_letter_topic: Option<String>,
+}
+
+/// Routes of a subscription according to Dapr specs.
+#[derive(Serialize, Default)]
+pub struct Routes {
+    /// Rules evaluated in order, the first matching rule determines the route.
+    pub rules: Vec<RoutingRule>,
+    /// Route of events not matching any rule.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+}
+
+/// Rule routing events matching a CEL expression to a path.
+#[derive(Serialize)]
+pub struct RoutingRule {
+    /// CEL expression, e.g. `event.type == "created"`.
+    #[serde(rename(serialize = "match"))]
+    pub match_expression: String,
+    /// Route of the events matching the expression.
+    pub path: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    struct CreatedHandler;
+
+    impl TopicHandler for CreatedHandler {
+        type Data = Value;
+
+        const TOPIC: &'static str = "catalog/product/changed";
+        const ROUTE: &'static str = "/on-product-creation-event";
+        const RULE: Option<&'static str> = Some(r#"event.type == "created""#);
+
+        async fn handle(_: HttpEventServiceState, _: Value) -> Result<(), EventError> {
+            Ok(())
+        }
+    }
+
+    struct ChangedHandler;
+
+    impl TopicHandler for ChangedHandler {
+        type Data = Value;
+
+        const PUBSUB_NAME: &'static str = "catalog-pubsub";
+        const TOPIC: &'static str = "catalog/product/changed";
+        const ROUTE: &'static str = "/on-product-change-event";
+
+        async fn handle(_: HttpEventServiceState, _: Value) -> Result<(), EventError> {
+            Ok(())
+        }
+    }
+
+    struct DuplicateRouteHandler;
+
+    impl TopicHandler for DuplicateRouteHandler {
+        type Data = Value;
+
+        const TOPIC: &'static str = "catalog/product/deleted";
+        const ROUTE: &'static str = "/on-product-creation-event";
+
+        async fn handle(_: HttpEventServiceState, _: Value) -> Result<(), EventError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn groups_rules_of_topic_into_one_subscription() {
+        struct DefaultHandler;
+
+        impl TopicHandler for DefaultHandler {
+            type Data = Value;
+
+            const TOPIC: &'static str = "catalog/product/changed";
+            const ROUTE: &'static str = "/on-product-event";
+
+            async fn handle(_: HttpEventServiceState, _: Value) -> Result<(), EventError> {
+                Ok(())
+            }
+        }
+
+        let registry = TopicRegistry::default()
+            .register::<CreatedHandler>()
+            .register::<DefaultHandler>();
+        assert_eq!(
+            serde_json::to_value(registry.subscriptions()).unwrap(),
+            json!([{
+                "pubsubName": "pubsub",
+                "topic": "catalog/product/changed",
+                "routes": {
+                    "rules": [{"match": r#"event.type == "created""#, "path": "/on-product-creation-event"}],
+                    "default": "/on-product-event",
+                },
+            }])
+        );
+        let handler = registry
+            .handler_for_topic("catalog/product/changed")
+            .unwrap();
+        assert_eq!(handler.route, "/on-product-event");
+    }
+
+    #[test]
+    fn subscribes_per_pubsub_component() {
+        let registry = TopicRegistry::default()
+            .register::<CreatedHandler>()
+            .register::<ChangedHandler>()
+            .with_dead_letter_topic("pubsub", "review/dead-letter", "/on-dead-letter-event");
+        let subscriptions = registry.subscriptions();
+        let pubsub_names: Vec<&str> = subscriptions
+            .iter()
+            .map(|subscription| subscription.pubsubname.as_str())
+            .collect();
+        assert_eq!(pubsub_names, vec!["pubsub", "catalog-pubsub", "pubsub"]);
+        assert_eq!(
+            subscriptions[1].dead_letter_topic.as_deref(),
+            Some("review/dead-letter")
+        );
+        assert_eq!(subscriptions[2].topic, "review/dead-letter");
+        assert_eq!(subscriptions[2].dead_letter_topic, None);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Route `/on-product-creation-event` is registered for multiple topic handlers."
+    )]
+    fn panics_on_duplicate_route() {
+        let _ = TopicRegistry::default()
+            .register::<CreatedHandler>()
+            .register::<DuplicateRouteHandler>();
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Route `/on-product-change-event` is registered for multiple topic handlers."
+    )]
+    fn panics_on_dead_letter_route_of_handler() {
+        let _ = TopicRegistry::default()
+            .register::<ChangedHandler>()
+            .with_dead_letter_topic("pubsub", "review/dead-letter", "/on-product-change-event");
+    }
+}
@@ -0,0 +1,51 @@
+use std::{any::type_name, collections::HashMap};
+
+use async_graphql::{dataloader::Loader, Error, Result};
+use bson::{doc, Uuid};
+use futures::TryStreamExt;
+use mongodb::Collection;
+use serde::de::DeserializeOwned;
+
+/// Batches loads of entities: `T` by UUID into a single `$in` query.
+pub struct EntityLoader<T> {
+    /// Collection of the entities.
+    collection: Collection<T>,
+    /// Returns the UUID of an entity.
+    id_of: fn(&T) -> Uuid,
+}
+
+impl<T> EntityLoader<T> {
+    /// Creates a loader for a collection.
+    ///
+    /// * `collection` - Collection of the entities.
+    /// * `id_of` - Returns the UUID of an entity.
+    pub fn new(collection: Collection<T>, id_of: fn(&T) -> Uuid) -> Self {
+        Self { collection, id_of }
+    }
+}
+
+impl<T> Loader<Uuid> for EntityLoader<T>
+where
+    T: DeserializeOwned + Unpin + Send + Sync + Clone + 'static,
+{
+    type Value = T;
+    type Error = Error;
+
+    async fn load(&self, keys: &[Uuid]) -> Result<HashMap<Uuid, T>> {
+        let filter = doc! {"_id": {"$in": keys.to_vec()}};
+        let maybe_entities: Result<Vec<T>, _> = match self.collection.find(filter, None).await {
+            Ok(cursor) => cursor.try_collect().await,
+            Err(error) => Err(error),
+        };
+        match maybe_entities {
+            Ok(entities) => Ok(entities
+                .into_iter()
+                .map(|entity| ((self.id_of)(&entity), entity))
+                .collect()),
+            Err(_) => {
+                let message = format!("Retrieving {} failed in MongoDB.", type_name::<T>());
+                Err(Error::new(message))
+            }
+        }
+    }
+}
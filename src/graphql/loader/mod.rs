@@ -0,0 +1,91 @@
+use async_graphql::{dataloader::DataLoader, ObjectType, SchemaBuilder, SubscriptionType};
+use bson::Uuid;
+use mongodb::Database;
+
+use entity_loader::EntityLoader;
+use rating_summary_loader::RatingSummaryLoader;
+use review_connection_loader::ReviewConnectionLoader;
+use reviewer_statistics_loader::ReviewerStatisticsLoader;
+
+use super::model::{product::Product, product_variant::ProductVariant, user::User};
+
+pub mod entity_loader;
+pub mod rating_summary_loader;
+pub mod review_connection_loader;
+pub mod reviewer_statistics_loader;
+
+/// Entity reviews can be retrieved for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReviewParent {
+    /// User owning the reviews.
+    User(Uuid),
+    /// Product with variants in review.
+    Product(Uuid),
+    /// Product variant in review.
+    ProductVariant(Uuid),
+}
+
+impl ReviewParent {
+    /// Returns the field of a review document referencing the parent.
+    pub fn field(&self) -> &'static str {
+        match self {
+            Self::User(_) => "user._id",
+            Self::Product(_) => "product_variant.product_id",
+            Self::ProductVariant(_) => "product_variant._id",
+        }
+    }
+
+    /// Returns the UUID of the parent.
+    pub fn id(&self) -> Uuid {
+        match self {
+            Self::User(id) | Self::Product(id) | Self::ProductVariant(id) => *id,
+        }
+    }
+}
+
+/// Adds the DataLoaders of entities, review connections, rating summaries and reviewer statistics to a schema.
+///
+/// Loaders do not cache, so that they can be shared between requests and only batch concurrent loads.
+///
+/// * `schema_builder` - Builder of the GraphQL schema.
+/// * `db_client` - MongoDB database client.
+pub fn register_loaders<Query, Mutation, Subscription>(
+    schema_builder: SchemaBuilder<Query, Mutation, Subscription>,
+    db_client: &Database,
+) -> SchemaBuilder<Query, Mutation, Subscription>
+where
+    Query: ObjectType + 'static,
+    Mutation: ObjectType + 'static,
+    Subscription: SubscriptionType + 'static,
+{
+    schema_builder
+        .data(DataLoader::new(
+            EntityLoader::new(db_client.collection::<User>("users"), |user| user._id),
+            tokio::spawn,
+        ))
+        .data(DataLoader::new(
+            EntityLoader::new(db_client.collection::<Product>("products"), |product| {
+                product._id
+            }),
+            tokio::spawn,
+        ))
+        .data(DataLoader::new(
+            EntityLoader::new(
+                db_client.collection::<ProductVariant>("product_variants"),
+                |product_variant| product_variant._id,
+            ),
+            tokio::spawn,
+        ))
+        .data(DataLoader::new(
+            ReviewConnectionLoader::new(db_client),
+            tokio::spawn,
+        ))
+        .data(DataLoader::new(
+            RatingSummaryLoader::new(db_client),
+            tokio::spawn,
+        ))
+        .data(DataLoader::new(
+            ReviewerStatisticsLoader::new(db_client),
+            tokio::spawn,
+        ))
+}
@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+
+use async_graphql::{
+    dataloader::{DataLoader, Loader},
+    Context, Error, Result,
+};
+use bson::{doc, Bson, Document, Uuid};
+use futures::TryStreamExt;
+use mongodb::{Collection, Database};
+use serde::Deserialize;
+
+use crate::graphql::{
+    model::review::{rating_value_expression, Rating},
+    response_cache::RESPONSE_CACHE,
+};
+
+use super::ReviewParent;
+
+/// Summary of the ratings of the visible reviews of a parent.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RatingSummary {
+    /// Number of visible reviews.
+    pub review_count: u64,
+    /// Average rating of visible reviews, `None` if there are no visible reviews.
+    pub average_rating: Option<f64>,
+}
+
+/// Rating summary of a parent as grouped by MongoDB.
+#[derive(Deserialize)]
+struct RatingSummaryGroup {
+    _id: Uuid,
+    review_count: u64,
+    average_rating: Option<f64>,
+}
+
+/// Batches loads of rating summaries into a single aggregation per kind of parent.
+///
+/// Hidden reviews are excluded regardless of the user, as for the average rating.
+pub struct RatingSummaryLoader {
+    collection: Collection<Document>,
+}
+
+impl RatingSummaryLoader {
+    /// Creates a loader for the reviews collection.
+    ///
+    /// * `db_client` - MongoDB database client.
+    pub fn new(db_client: &Database) -> Self {
+        Self {
+            collection: db_client.collection::<Document>("reviews"),
+        }
+    }
+
+    /// Aggregates the rating summaries of parents of the same kind.
+    ///
+    /// * `field` - Field of a review document referencing the parents.
+    /// * `ids` - UUIDs of the parents.
+    async fn load_summaries(
+        &self,
+        field: &str,
+        ids: Vec<Uuid>,
+    ) -> mongodb::error::Result<Vec<RatingSummaryGroup>> {
+        let pipeline = vec![
+            doc! {"$match": {field: {"$in": ids}, "is_visible": true}},
+            doc! {"$group": {
+                "_id": format!("${}", field),
+                "review_count": {"$sum": 1},
+                "average_rating": {"$avg": rating_value_expression()},
+            }},
+        ];
+        let documents: Vec<Document> = self
+            .collection
+            .aggregate(pipeline, None)
+            .await?
+            .try_collect()
+            .await?;
+        documents
+            .into_iter()
+            .map(|document| Ok(bson::from_document(document)?))
+            .collect()
+    }
+}
+
+/// Loads the rating summary of a parent, cached by the response cache.
+///
+/// * `ctx` - GraphQL context containing the rating summary loader.
+/// * `parent` - Entity the rating summary is calculated for.
+pub async fn load_rating_summary(ctx: &Context<'_>, parent: ReviewParent) -> Result<RatingSummary> {
+    let rating_summary_loader = ctx.data::<DataLoader<RatingSummaryLoader>>()?;
+    RESPONSE_CACHE
+        .rating_summary(&parent, async {
+            let maybe_rating_summary = rating_summary_loader.load_one(parent).await?;
+            Ok(maybe_rating_summary.unwrap_or_default())
+        })
+        .await
+}
+
+/// Returns the MongoDB aggregation stages adding the fields `review_count` and `average_rating`
+/// of the visible reviews to each entity.
+///
+/// The visible reviews are grouped once by entity and joined onto the entities,
+/// each entity is nested in the field `entity`, which should be replaced into the root afterwards.
+///
+/// * `review_field` - Field of a review document referencing the entities.
+/// * `rating` - Only counts reviews of this rating if set.
+pub fn rating_summary_stages(review_field: &str, rating: Option<Rating>) -> Vec<Document> {
+    let mut review_filter = doc! {"is_visible": true};
+    if let Some(definitely_rating) = rating {
+        review_filter.insert("rating", definitely_rating);
+    }
+    vec![
+        doc! {"$project": {"entity": "$$ROOT"}},
+        doc! {"$unionWith": {
+            "coll": "reviews",
+            "pipeline": [
+                {"$match": review_filter},
+                {"$group": {
+                    "_id": format!("${}", review_field),
+                    "review_count": {"$sum": 1},
+                    "average_rating": {"$avg": rating_value_expression()},
+                }},
+            ],
+        }},
+        doc! {"$group": {
+            "_id": "$_id",
+            "entity": {"$max": "$entity"},
+            "review_count": {"$max": "$review_count"},
+            "average_rating": {"$max": "$average_rating"},
+        }},
+        doc! {"$match": {"entity": {"$ne": Bson::Null}}},
+        doc! {"$addFields": {"review_count": {"$ifNull": ["$review_count", 0]}}},
+    ]
+}
+
+impl Loader<ReviewParent> for RatingSummaryLoader {
+    type Value = RatingSummary;
+    type Error = Error;
+
+    async fn load(&self, keys: &[ReviewParent]) -> Result<HashMap<ReviewParent, RatingSummary>> {
+        let mut parents_by_field: HashMap<&str, Vec<ReviewParent>> = HashMap::new();
+        for parent in keys {
+            parents_by_field
+                .entry(parent.field())
+                .or_default()
+                .push(*parent);
+        }
+        let mut rating_summaries = HashMap::new();
+        for (field, parents) in parents_by_field {
+            let ids = parents.iter().map(ReviewParent::id).collect();
+            let groups = self
+                .load_summaries(field, ids)
+                .await
+                .map_err(|_| Error::new("Calculating rating summaries failed in MongoDB."))?;
+            let mut summaries_by_id: HashMap<Uuid, RatingSummary> = groups
+                .into_iter()
+                .map(|group| {
+                    let rating_summary = RatingSummary {
+                        review_count: group.review_count,
+                        average_rating: group.average_rating,
+                    };
+                    (group._id, rating_summary)
+                })
+                .collect();
+            for parent in parents {
+                let rating_summary = summaries_by_id.remove(&parent.id()).unwrap_or_default();
+                rating_summaries.insert(parent, rating_summary);
+            }
+        }
+        Ok(rating_summaries)
+    }
+}
@@ -0,0 +1,278 @@
+use std::collections::HashMap;
+
+use async_graphql::{dataloader::Loader, Context, Error, Result};
+use bson::{doc, Bson, Document, Uuid};
+use futures::TryStreamExt;
+use mongodb::{options::AggregateOptions, Collection, Database};
+use serde::Deserialize;
+
+use crate::authorization::{authorize_user, permission::Permission};
+use crate::graphql::{
+    model::{
+        connection::{base_connection::aggregate_connection, review_connection::ReviewConnection},
+        order_datatypes::{ReviewOrder, ReviewOrderField, ReviewOrderInput},
+        review::{Review, ReviewVisibility},
+    },
+    query_limits::{page_size, review_skip},
+};
+
+use super::ReviewParent;
+
+/// Page of reviews requested from a review connection, independent of its parent.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ReviewPage {
+    /// Number of reviews to skip at the beginning.
+    pub skip: u64,
+    /// Number of reviews to retrieve.
+    pub limit: i64,
+    /// Order in which reviews are retrieved.
+    pub order: ReviewOrder,
+    /// Only retrieves reviews of product variants in this product category if set.
+    pub product_category: Option<String>,
+    /// Flag if anonymous reviews are retrieved.
+    pub include_anonymous: bool,
+    /// Reviews visible to the requesting user.
+    pub visibility: ReviewVisibility,
+}
+
+impl ReviewPage {
+    /// Creates a page from the arguments of a review connection field.
+    ///
+    /// * `ctx` - GraphQL context which may contain the `Authorized-User` header.
+    /// * `first` - Number of reviews requested by the `first` argument.
+    /// * `skip` - Number of reviews to skip at the beginning, at most `MAX_REVIEW_SKIP`.
+    /// * `order_by` - Order in which reviews are retrieved, later inputs break ties of earlier ones,
+    ///   only users with the `review:moderate` permission can order by "user_id".
+    pub fn new(
+        ctx: &Context,
+        first: Option<u32>,
+        skip: Option<u64>,
+        order_by: Option<Vec<ReviewOrderInput>>,
+    ) -> Result<Self> {
+        let order = ReviewOrder::new(order_by)?;
+        if order.orders_by(ReviewOrderField::UserId) {
+            authorize_user(ctx, None, Permission::ModerateReviews)?;
+        }
+        Ok(Self {
+            skip: review_skip(skip)?,
+            limit: page_size(first)?,
+            order,
+            product_category: None,
+            include_anonymous: true,
+            visibility: ReviewVisibility::of(ctx),
+        })
+    }
+
+    /// Restricts a MongoDB review filter to the reviews of this page.
+    ///
+    /// * `filter` - MongoDB filter of reviews to restrict.
+    fn restrict(&self, filter: &mut Document) {
+        if !self.include_anonymous {
+            filter.insert("is_anonymous", doc! {"$ne": true});
+        }
+        if let Some(definitely_product_category) = &self.product_category {
+            filter.insert(
+                "product_variant.product_category",
+                definitely_product_category,
+            );
+        }
+        self.visibility.restrict(filter);
+    }
+}
+
+/// Key of a review connection, the page of reviews of a parent.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ReviewConnectionKey {
+    /// Entity the reviews are retrieved for.
+    pub parent: ReviewParent,
+    /// Page of reviews to retrieve.
+    pub page: ReviewPage,
+}
+
+/// Page of review UUIDs of a parent as grouped by MongoDB.
+#[derive(Deserialize)]
+struct ReviewIdGroup {
+    _id: Uuid,
+    review_ids: Vec<Uuid>,
+    total_count: u64,
+}
+
+/// Page of reviews of a parent.
+struct ReviewGroup {
+    reviews: Vec<Review>,
+    total_count: u64,
+}
+
+/// Batches loads of review connections into a single aggregation per kind of parent and page.
+pub struct ReviewConnectionLoader {
+    collection: Collection<Document>,
+}
+
+impl ReviewConnectionLoader {
+    /// Creates a loader for the reviews collection.
+    ///
+    /// * `db_client` - MongoDB database client.
+    pub fn new(db_client: &Database) -> Self {
+        Self {
+            collection: db_client.collection::<Document>("reviews"),
+        }
+    }
+
+    /// Aggregates the same page of reviews for parents of the same kind.
+    ///
+    /// Each group only collects the UUIDs of the first `skip + limit` reviews with `$firstN` (MongoDB 5.2 or later),
+    /// so that the size of a group is bounded by `MAX_REVIEW_SKIP` and `MAX_PAGE_SIZE` UUIDs
+    /// instead of the reviews of its parent. The reviews of all pages are retrieved afterwards in a single query.
+    ///
+    /// * `field` - Field of a review document referencing the parents.
+    /// * `ids` - UUIDs of the parents.
+    /// * `page` - Page of reviews to retrieve for each parent.
+    async fn load_pages(
+        &self,
+        field: &str,
+        ids: Vec<Uuid>,
+        page: &ReviewPage,
+    ) -> mongodb::error::Result<HashMap<Uuid, ReviewGroup>> {
+        let mut filter = doc! {field: {"$in": ids}};
+        page.restrict(&mut filter);
+        let mut group = doc! {
+            "_id": format!("${}", field),
+            "total_count": {"$sum": 1},
+        };
+        let skip = i64::try_from(page.skip).unwrap_or(i64::MAX);
+        let review_ids_expression = match skip.checked_add(page.limit) {
+            Some(n) if page.limit > 0 => {
+                group.insert("review_ids", doc! {"$firstN": {"input": "$_id", "n": n}});
+                Bson::Document(doc! {"$slice": ["$review_ids", skip, page.limit]})
+            }
+            _ => Bson::Document(doc! {"$literal": []}),
+        };
+        let mut pipeline = vec![doc! {"$match": filter}];
+        pipeline.extend(page.order.sort_stages());
+        pipeline.extend([
+            doc! {"$group": group},
+            doc! {"$project": {"review_ids": review_ids_expression, "total_count": 1}},
+        ]);
+        let options = AggregateOptions::builder().allow_disk_use(true).build();
+        let documents: Vec<Document> = self
+            .collection
+            .aggregate(pipeline, options)
+            .await?
+            .try_collect()
+            .await?;
+        let id_groups = documents
+            .into_iter()
+            .map(bson::from_document::<ReviewIdGroup>)
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut reviews_by_id = self.find_reviews(&id_groups).await?;
+        let groups = id_groups
+            .into_iter()
+            .map(|id_group| {
+                let reviews = id_group
+                    .review_ids
+                    .iter()
+                    .filter_map(|review_id| reviews_by_id.remove(review_id))
+                    .collect();
+                let group = ReviewGroup {
+                    reviews,
+                    total_count: id_group.total_count,
+                };
+                (id_group._id, group)
+            })
+            .collect();
+        Ok(groups)
+    }
+
+    /// Retrieves the reviews of the pages of review UUIDs.
+    ///
+    /// * `id_groups` - Pages of review UUIDs of parents.
+    async fn find_reviews(
+        &self,
+        id_groups: &[ReviewIdGroup],
+    ) -> mongodb::error::Result<HashMap<Uuid, Review>> {
+        let review_ids: Vec<Uuid> = id_groups
+            .iter()
+            .flat_map(|id_group| id_group.review_ids.iter().copied())
+            .collect();
+        if review_ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let documents: Vec<Document> = self
+            .collection
+            .find(doc! {"_id": {"$in": review_ids}}, None)
+            .await?
+            .try_collect()
+            .await?;
+        documents
+            .into_iter()
+            .map(|document| {
+                let review: Review = bson::from_document(document)?;
+                Ok((review._id, review))
+            })
+            .collect()
+    }
+}
+
+impl Loader<ReviewConnectionKey> for ReviewConnectionLoader {
+    type Value = ReviewConnection;
+    type Error = Error;
+
+    async fn load(
+        &self,
+        keys: &[ReviewConnectionKey],
+    ) -> Result<HashMap<ReviewConnectionKey, ReviewConnection>> {
+        let mut parents_by_page: HashMap<(&str, &ReviewPage), Vec<ReviewParent>> = HashMap::new();
+        for key in keys {
+            parents_by_page
+                .entry((key.parent.field(), &key.page))
+                .or_default()
+                .push(key.parent);
+        }
+        let mut review_connections = HashMap::new();
+        for ((field, page), parents) in parents_by_page {
+            let ids = parents.iter().map(ReviewParent::id).collect();
+            let mut groups_by_id = self
+                .load_pages(field, ids, page)
+                .await
+                .map_err(|_| Error::new("Retrieving reviews failed in MongoDB."))?;
+            for parent in parents {
+                let review_connection = match groups_by_id.remove(&parent.id()) {
+                    Some(group) => ReviewConnection {
+                        has_next_page: page.skip + (group.reviews.len() as u64) < group.total_count,
+                        nodes: group.reviews,
+                        total_count: group.total_count,
+                    },
+                    None => ReviewConnection {
+                        nodes: Vec::new(),
+                        has_next_page: false,
+                        total_count: 0,
+                    },
+                };
+                let key = ReviewConnectionKey {
+                    parent,
+                    page: page.clone(),
+                };
+                review_connections.insert(key, review_connection);
+            }
+        }
+        Ok(review_connections)
+    }
+}
+
+/// Retrieves a page of all reviews, regardless of their parent.
+///
+/// Not batched, since the query retrieves a single page.
+///
+/// * `db_client` - MongoDB database client.
+/// * `page` - Page of reviews to retrieve.
+pub async fn load_all_reviews(db_client: &Database, page: &ReviewPage) -> Result<ReviewConnection> {
+    let collection = db_client.collection::<Document>("reviews");
+    let mut filter = doc! {};
+    page.restrict(&mut filter);
+    let mut pipeline = vec![doc! {"$match": filter}];
+    pipeline.extend(page.order.sort_stages());
+    let connection = aggregate_connection::<Review>(&collection, pipeline, page.skip, page.limit)
+        .await
+        .map_err(|_| Error::new("Retrieving reviews failed in MongoDB."))?;
+    Ok(connection.into())
+}
@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+
+use async_graphql::{dataloader::Loader, Error, Result};
+use bson::{doc, DateTime, Document, Uuid};
+use futures::TryStreamExt;
+use mongodb::{Collection, Database};
+use serde::Deserialize;
+
+use crate::graphql::model::{
+    review::rating_value_expression,
+    reviewer_statistics::{ReviewerStatistics, REVIEWER_RANK_THRESHOLDS},
+};
+
+/// Reviewer statistics of a user as grouped by MongoDB.
+#[derive(Deserialize)]
+struct ReviewerStatisticsGroup {
+    _id: Uuid,
+    review_count: u64,
+    average_given_rating: Option<f64>,
+    helpful_vote_count: u64,
+    first_reviewed_at: DateTime,
+    last_reviewed_at: DateTime,
+}
+
+/// Batches loads of reviewer statistics into a single aggregation.
+///
+/// Only visible reviews which are not anonymous are included regardless of the user,
+/// so that the statistics do not reveal anonymous or hidden reviews.
+pub struct ReviewerStatisticsLoader {
+    collection: Collection<Document>,
+}
+
+impl ReviewerStatisticsLoader {
+    /// Creates a loader for the reviews collection.
+    ///
+    /// * `db_client` - MongoDB database client.
+    pub fn new(db_client: &Database) -> Self {
+        Self {
+            collection: db_client.collection::<Document>("reviews"),
+        }
+    }
+
+    /// Aggregates the reviewer statistics of users.
+    ///
+    /// * `ids` - UUIDs of the users.
+    async fn load_statistics(
+        &self,
+        ids: Vec<Uuid>,
+    ) -> mongodb::error::Result<Vec<ReviewerStatisticsGroup>> {
+        let pipeline = vec![
+            doc! {"$match": {
+                "user._id": {"$in": ids},
+                "is_visible": true,
+                "is_anonymous": {"$ne": true},
+            }},
+            doc! {"$group": {
+                "_id": "$user._id",
+                "review_count": {"$sum": 1},
+                "average_given_rating": {"$avg": rating_value_expression()},
+                "helpful_vote_count": {"$sum": {"$ifNull": ["$helpful_vote_count", 0]}},
+                "first_reviewed_at": {"$min": "$created_at"},
+                "last_reviewed_at": {"$max": "$created_at"},
+            }},
+        ];
+        let documents: Vec<Document> = self
+            .collection
+            .aggregate(pipeline, None)
+            .await?
+            .try_collect()
+            .await?;
+        documents
+            .into_iter()
+            .map(|document| Ok(bson::from_document(document)?))
+            .collect()
+    }
+}
+
+impl Loader<Uuid> for ReviewerStatisticsLoader {
+    type Value = ReviewerStatistics;
+    type Error = Error;
+
+    async fn load(&self, keys: &[Uuid]) -> Result<HashMap<Uuid, ReviewerStatistics>> {
+        let groups = self
+            .load_statistics(keys.to_vec())
+            .await
+            .map_err(|_| Error::new("Calculating reviewer statistics failed in MongoDB."))?;
+        let reviewer_statistics = groups
+            .into_iter()
+            .map(|group| {
+                let statistics = ReviewerStatistics {
+                    review_count: group.review_count,
+                    average_given_rating: group
+                        .average_given_rating
+                        .map(|average_given_rating| average_given_rating as f32),
+                    helpful_vote_count: group.helpful_vote_count,
+                    first_reviewed_at: Some(group.first_reviewed_at),
+                    last_reviewed_at: Some(group.last_reviewed_at),
+                    rank: REVIEWER_RANK_THRESHOLDS
+                        .rank_of(group.review_count, group.helpful_vote_count),
+                };
+                (group._id, statistics)
+            })
+            .collect();
+        Ok(reviewer_statistics)
+    }
+}
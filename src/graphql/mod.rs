@@ -1,4 +1,10 @@
+pub mod loader;
 pub mod model;
 pub mod mutation;
 pub mod mutation_input_structs;
+pub mod persisted_queries;
 pub mod query;
+pub mod query_limits;
+pub mod rate_limit;
+pub mod response_cache;
+pub mod user_data_export;
@@ -1,5 +1,9 @@
 use async_graphql::{OutputType, SimpleObject};
+use bson::{doc, Document};
+use futures::TryStreamExt;
+use mongodb::{options::AggregateOptions, Collection};
 use mongodb_cursor_pagination::FindResult;
+use serde::{de::DeserializeOwned, Deserialize};
 
 /// A base connection for an output type.
 #[derive(SimpleObject)]
@@ -15,7 +19,64 @@ pub struct BaseConnection<T: OutputType> {
 
 pub struct FindResultWrapper<Node>(pub FindResult<Node>);
 
+/// Page of nodes and their total count as computed by a MongoDB `$facet` stage.
+#[derive(Deserialize)]
+struct FacetResult<Node> {
+    #[serde(default = "Vec::new")]
+    nodes: Vec<Node>,
+    #[serde(default)]
+    total_count: Vec<FacetCount>,
+}
+
+/// Number of nodes as counted by MongoDB.
+#[derive(Deserialize)]
+struct FacetCount {
+    count: u64,
+}
+
+/// Aggregates a page of nodes and their total count by appending a `$facet` stage to a pipeline.
+///
+/// * `collection` - Collection to aggregate.
+/// * `pipeline` - Stages filtering and sorting the nodes.
+/// * `skip` - Number of nodes to skip at the beginning.
+/// * `limit` - Number of nodes to retrieve.
+pub async fn aggregate_connection<Node>(
+    collection: &Collection<Document>,
+    mut pipeline: Vec<Document>,
+    skip: u64,
+    limit: i64,
+) -> mongodb::error::Result<BaseConnection<Node>>
+where
+    Node: OutputType + DeserializeOwned,
+{
+    let node_stages = match limit {
+        0 => vec![doc! {"$match": {"$expr": false}}],
+        limit => vec![doc! {"$skip": skip as i64}, doc! {"$limit": limit}],
+    };
+    pipeline.push(doc! {"$facet": {
+        "nodes": node_stages,
+        "total_count": [{"$count": "count"}],
+    }});
+    let options = AggregateOptions::builder().allow_disk_use(true).build();
+    let maybe_document = collection
+        .aggregate(pipeline, options)
+        .await?
+        .try_next()
+        .await?;
+    let facet_result: FacetResult<Node> = bson::from_document(maybe_document.unwrap_or_default())?;
+    let total_count = facet_result
+        .total_count
+        .first()
+        .map_or(0, |facet_count| facet_count.count);
+    Ok(BaseConnection {
+        has_next_page: skip + (facet_result.nodes.len() as u64) < total_count,
+        nodes: facet_result.nodes,
+        total_count,
+    })
+}
+
 /// Object that writes total count of items in a query, regardless of pagination.
+#[allow(dead_code)]
 #[derive(SimpleObject)]
 pub struct AdditionalFields {
     total_count: u64,
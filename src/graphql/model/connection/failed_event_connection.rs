@@ -0,0 +1,28 @@
+use async_graphql::SimpleObject;
+
+use super::{super::failed_event::FailedEvent, base_connection::BaseConnection};
+
+/// A connection of failed events.
+#[derive(Debug, SimpleObject, Clone)]
+#[graphql(shareable)]
+pub struct FailedEventConnection {
+    /// The resulting entities.
+    pub nodes: Vec<FailedEvent>,
+    /// Whether this connection has a next page.
+    pub has_next_page: bool,
+    /// The total amount of items in this connection.
+    pub total_count: u64,
+}
+
+/// Implementation of conversion from `BaseConnection<FailedEvent>` to `FailedEventConnection`.
+///
+/// Prevents GraphQL naming conflicts.
+impl From<BaseConnection<FailedEvent>> for FailedEventConnection {
+    fn from(value: BaseConnection<FailedEvent>) -> Self {
+        Self {
+            nodes: value.nodes,
+            has_next_page: value.has_next_page,
+            total_count: value.total_count,
+        }
+    }
+}
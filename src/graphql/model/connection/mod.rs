@@ -1,2 +1,6 @@
 pub mod base_connection;
+pub mod failed_event_connection;
+pub mod product_connection;
+pub mod product_variant_connection;
 pub mod review_connection;
+pub mod user_connection;
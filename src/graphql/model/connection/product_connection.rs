@@ -0,0 +1,28 @@
+use async_graphql::SimpleObject;
+
+use super::{super::product::Product, base_connection::BaseConnection};
+
+/// A connection of products.
+#[derive(Debug, SimpleObject, Clone)]
+#[graphql(shareable)]
+pub struct ProductConnection {
+    /// The resulting entities.
+    pub nodes: Vec<Product>,
+    /// Whether this connection has a next page.
+    pub has_next_page: bool,
+    /// The total amount of items in this connection.
+    pub total_count: u64,
+}
+
+/// Implementation of conversion from `BaseConnection<Product>` to `ProductConnection`.
+///
+/// Prevents GraphQL naming conflicts.
+impl From<BaseConnection<Product>> for ProductConnection {
+    fn from(value: BaseConnection<Product>) -> Self {
+        Self {
+            nodes: value.nodes,
+            has_next_page: value.has_next_page,
+            total_count: value.total_count,
+        }
+    }
+}
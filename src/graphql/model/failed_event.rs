@@ -0,0 +1,65 @@
+use async_graphql::{ComplexObject, SimpleObject};
+use bson::{datetime::DateTime, Bson};
+use serde::{Deserialize, Serialize};
+
+use crate::authorization::{permission::Permission, PermissionGuard};
+
+/// Event which could not be processed, stored for inspection and replay.
+///
+/// Data and error details may contain personal data and are only resolved for users with the `event:manage` permission.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, SimpleObject)]
+#[graphql(complex)]
+pub struct FailedEvent {
+    /// Id of the CloudEvent.
+    pub _id: String,
+    /// Topic the event was originally published on, the dead-letter topic if it is unknown.
+    pub topic: String,
+    /// Data of the event.
+    #[graphql(skip)]
+    pub data: Bson,
+    /// Description of the last error while processing the event.
+    #[graphql(guard = "PermissionGuard(Permission::ManageEvents)")]
+    pub error: String,
+    /// Flag if the last error is transient, so that a replay may succeed without changes.
+    pub retryable: bool,
+    /// Number of failed processing attempts.
+    pub attempts: i32,
+    /// Flag if Dapr has given up redelivering the event and sent it to the dead-letter topic.
+    pub dead_lettered: bool,
+    /// Flag if the original topic of the event is known, so that it can be replayed.
+    ///
+    /// Events which were dead-lettered without a recorded failure only carry the dead-letter topic.
+    pub replayable: bool,
+    /// Timestamp when processing the event failed for the first time.
+    pub first_failed_at: DateTime,
+    /// Timestamp when processing the event failed for the last time.
+    pub last_failed_at: DateTime,
+}
+
+/// Outcome of replaying multiple failed events.
+#[derive(Debug, Clone, Default, SimpleObject)]
+pub struct FailedEventReplay {
+    /// Number of events which were replayed successfully and removed.
+    pub replayed_count: u64,
+    /// Events whose replay failed again.
+    pub failures: Vec<FailedEventReplayFailure>,
+}
+
+/// Failed replay of a failed event.
+#[derive(Debug, Clone, SimpleObject)]
+pub struct FailedEventReplayFailure {
+    /// Id of the CloudEvent.
+    pub id: String,
+    /// Description of the error while replaying the event.
+    #[graphql(guard = "PermissionGuard(Permission::ManageEvents)")]
+    pub error: String,
+}
+
+#[ComplexObject]
+impl FailedEvent {
+    /// Data of the event as JSON string.
+    #[graphql(guard = "PermissionGuard(Permission::ManageEvents)")]
+    async fn data(&self) -> String {
+        self.data.clone().into_relaxed_extjson().to_string()
+    }
+}
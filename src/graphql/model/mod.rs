@@ -1,6 +1,11 @@
 pub mod connection;
+pub mod failed_event;
 pub mod order_datatypes;
 pub mod product;
 pub mod product_variant;
+pub mod rating_trend;
 pub mod review;
+pub mod review_record;
+pub mod review_vote;
+pub mod reviewer_statistics;
 pub mod user;
@@ -1,20 +1,20 @@
-use async_graphql::{Enum, InputObject, SimpleObject};
+use async_graphql::{Enum, Error, InputObject, InputType, Result, SimpleObject};
+use bson::{doc, Bson, Document};
+
+use super::review::{
+    helpfulness_score_expression, rating_value_expression, relevance_score_expression, Rating,
+};
 
 /// GraphQL order direction.
-#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
 pub enum OrderDirection {
     /// Ascending order direction.
+    #[default]
     Asc,
     /// Descending order direction.
     Desc,
 }
 
-impl Default for OrderDirection {
-    fn default() -> Self {
-        Self::Asc
-    }
-}
-
 /// Implements conversion to `i32`` for MongoDB document sorting.
 impl From<OrderDirection> for i32 {
     fn from(value: OrderDirection) -> Self {
@@ -26,18 +26,26 @@ impl From<OrderDirection> for i32 {
 }
 
 /// Describes the fields that a review can be ordered by.
-#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
 pub enum ReviewOrderField {
     /// Orders by "id".
+    #[default]
     Id,
-    /// Orders by "user_id".
+    /// Orders by "user_id", only allowed for users with the `review:moderate` permission,
+    /// since the order would reveal the authors of anonymous reviews.
     UserId,
     /// Orders by "product_variant".
     ProductVariant,
-    /// Orders by "rating".
+    /// Orders by "rating" in number of stars.
     Rating,
     /// Orders by "created_at".
     CreatedAt,
+    /// Orders by "last_updated_at".
+    UpdatedAt,
+    /// Orders by the lower bound of the Wilson score interval of helpful votes, by default descending, i.e. most helpful first.
+    MostHelpful,
+    /// Orders by relevance weighted by recency, body length, verified purchase and attached media, by default descending, i.e. most relevant first.
+    MostRelevant,
 }
 
 impl ReviewOrderField {
@@ -46,22 +54,39 @@ impl ReviewOrderField {
             ReviewOrderField::Id => "_id",
             ReviewOrderField::UserId => "user",
             ReviewOrderField::ProductVariant => "product_variant",
-            ReviewOrderField::Rating => "rating",
-            ReviewOrderField::CreatedAt => "last_updated_at",
+            ReviewOrderField::Rating => "rating_value",
+            ReviewOrderField::CreatedAt => "created_at",
+            ReviewOrderField::UpdatedAt => "last_updated_at",
+            ReviewOrderField::MostHelpful => "helpfulness_score",
+            ReviewOrderField::MostRelevant => "relevance_score",
         }
     }
-}
 
-impl Default for ReviewOrderField {
-    fn default() -> Self {
-        Self::Id
+    /// Returns the direction reviews are ordered in if no direction is specified.
+    ///
+    /// Scores are ordered descending, so that the best reviews come first, all other fields ascending.
+    pub fn default_direction(&self) -> OrderDirection {
+        match self {
+            ReviewOrderField::MostHelpful | ReviewOrderField::MostRelevant => OrderDirection::Desc,
+            _ => OrderDirection::Asc,
+        }
+    }
+
+    /// Returns the MongoDB aggregation expression computing the field if it is not stored in review documents.
+    pub fn computed_expression(&self) -> Option<Document> {
+        match self {
+            ReviewOrderField::Rating => Some(rating_value_expression()),
+            ReviewOrderField::MostHelpful => Some(helpfulness_score_expression()),
+            ReviewOrderField::MostRelevant => Some(relevance_score_expression()),
+            _ => None,
+        }
     }
 }
 
-/// Specifies the order of reviews.
+/// Specifies the order of reviews by one field.
 #[derive(SimpleObject, InputObject)]
 pub struct ReviewOrderInput {
-    /// Order direction of reviews.
+    /// Order direction of reviews, by default descending for scores and ascending for all other fields.
     pub direction: Option<OrderDirection>,
     /// Field that reviews should be ordered by.
     pub field: Option<ReviewOrderField>,
@@ -76,36 +101,114 @@ impl Default for ReviewOrderInput {
     }
 }
 
+/// Validated order of reviews by multiple fields, ending with "id" as tiebreaker.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ReviewOrder {
+    clauses: Vec<(ReviewOrderField, OrderDirection)>,
+}
+
+impl ReviewOrder {
+    /// Creates the order of reviews from order inputs, where later inputs break ties of earlier ones.
+    ///
+    /// Ascending "id" is appended if reviews are not ordered by "id", so that pages are deterministic.
+    ///
+    /// * `order_by` - Order inputs, by default ascending "id".
+    pub fn new(order_by: Option<Vec<ReviewOrderInput>>) -> Result<Self> {
+        let mut clauses: Vec<(ReviewOrderField, OrderDirection)> = Vec::new();
+        for review_order in order_by.unwrap_or_default() {
+            let field = review_order.field.unwrap_or_default();
+            if clauses
+                .iter()
+                .any(|(ordered_field, _)| *ordered_field == field)
+            {
+                let message = format!(
+                    "Review order field: `{}` is used more than once.",
+                    field.to_value()
+                );
+                return Err(Error::new(message));
+            }
+            let direction = review_order
+                .direction
+                .unwrap_or_else(|| field.default_direction());
+            clauses.push((field, direction));
+        }
+        if !clauses
+            .iter()
+            .any(|(field, _)| *field == ReviewOrderField::Id)
+        {
+            clauses.push((ReviewOrderField::Id, OrderDirection::Asc));
+        }
+        Ok(Self { clauses })
+    }
+
+    /// Checks if reviews are ordered by a field.
+    ///
+    /// * `field` - Field to check.
+    pub fn orders_by(&self, field: ReviewOrderField) -> bool {
+        self.clauses
+            .iter()
+            .any(|(ordered_field, _)| *ordered_field == field)
+    }
+
+    /// Returns the MongoDB aggregation stages sorting reviews in this order.
+    ///
+    /// Computed fields are added before and removed after sorting.
+    pub fn sort_stages(&self) -> Vec<Document> {
+        let mut computed_fields = Document::new();
+        let mut sort = Document::new();
+        for (field, direction) in &self.clauses {
+            if let Some(expression) = field.computed_expression() {
+                computed_fields.insert(field.as_str(), expression);
+            }
+            sort.insert(field.as_str(), i32::from(*direction));
+        }
+        if computed_fields.is_empty() {
+            return vec![doc! {"$sort": sort}];
+        }
+        let projection: Document = computed_fields
+            .keys()
+            .map(|field| (field.clone(), Bson::Int32(0)))
+            .collect();
+        vec![
+            doc! {"$addFields": computed_fields},
+            doc! {"$sort": sort},
+            doc! {"$project": projection},
+        ]
+    }
+}
+
 /// Describes the fields that a foreign types can be ordered by.
-///
-/// Only the id valid at the moment.
-#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Default)]
 pub enum CommonOrderField {
     /// Orders by "id".
+    #[default]
     Id,
+    /// Orders by the number of visible reviews.
+    ReviewCount,
+    /// Orders by the average rating of visible reviews, foreign types without reviews first in ascending order.
+    AverageRating,
 }
 
 impl CommonOrderField {
     pub fn as_str(&self) -> &'static str {
         match self {
             CommonOrderField::Id => "_id",
+            CommonOrderField::ReviewCount => "review_count",
+            CommonOrderField::AverageRating => "average_rating",
         }
     }
 }
 
-impl Default for CommonOrderField {
-    fn default() -> Self {
-        Self::Id
-    }
-}
-
 /// Specifies the order of foreign types.
 #[derive(SimpleObject, InputObject)]
 pub struct CommonOrderInput {
-    /// Order direction of reviews.
+    /// Order direction of foreign types.
     pub direction: Option<OrderDirection>,
-    /// Field that reviews should be ordered by.
+    /// Field that foreign types should be ordered by.
     pub field: Option<CommonOrderField>,
+    /// Only counts reviews of this rating when ordering by review count, e.g. to find products with the most 1-star reviews.
+    /// Rejected for all other fields, since the average rating of reviews of one rating is the rating itself.
+    pub rating: Option<Rating>,
 }
 
 impl Default for CommonOrderInput {
@@ -113,6 +216,137 @@ impl Default for CommonOrderInput {
         Self {
             direction: Some(Default::default()),
             field: Some(Default::default()),
+            rating: None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order_input(field: ReviewOrderField, direction: Option<OrderDirection>) -> ReviewOrderInput {
+        ReviewOrderInput {
+            direction,
+            field: Some(field),
+        }
+    }
+
+    #[test]
+    fn orders_by_ascending_id_by_default() {
+        let order = ReviewOrder::new(None).unwrap();
+        assert_eq!(
+            order.clauses,
+            vec![(ReviewOrderField::Id, OrderDirection::Asc)]
+        );
+    }
+
+    #[test]
+    fn appends_id_as_tiebreaker() {
+        let order = ReviewOrder::new(Some(vec![
+            order_input(ReviewOrderField::Rating, Some(OrderDirection::Desc)),
+            order_input(ReviewOrderField::CreatedAt, Some(OrderDirection::Asc)),
+        ]))
+        .unwrap();
+        assert_eq!(
+            order.clauses,
+            vec![
+                (ReviewOrderField::Rating, OrderDirection::Desc),
+                (ReviewOrderField::CreatedAt, OrderDirection::Asc),
+                (ReviewOrderField::Id, OrderDirection::Asc),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_explicit_id_order() {
+        let order = ReviewOrder::new(Some(vec![
+            order_input(ReviewOrderField::Id, Some(OrderDirection::Desc)),
+            order_input(ReviewOrderField::Rating, None),
+        ]))
+        .unwrap();
+        assert_eq!(
+            order.clauses,
+            vec![
+                (ReviewOrderField::Id, OrderDirection::Desc),
+                (ReviewOrderField::Rating, OrderDirection::Asc),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_duplicate_fields() {
+        let error = ReviewOrder::new(Some(vec![
+            order_input(ReviewOrderField::Rating, Some(OrderDirection::Asc)),
+            order_input(ReviewOrderField::Rating, Some(OrderDirection::Desc)),
+        ]))
+        .unwrap_err();
+        assert_eq!(
+            error.message,
+            "Review order field: `RATING` is used more than once."
+        );
+    }
+
+    #[test]
+    fn orders_scores_descending_by_default() {
+        let order = ReviewOrder::new(Some(vec![
+            order_input(ReviewOrderField::MostHelpful, None),
+            order_input(ReviewOrderField::MostRelevant, None),
+        ]))
+        .unwrap();
+        assert_eq!(
+            order.clauses,
+            vec![
+                (ReviewOrderField::MostHelpful, OrderDirection::Desc),
+                (ReviewOrderField::MostRelevant, OrderDirection::Desc),
+                (ReviewOrderField::Id, OrderDirection::Asc),
+            ]
+        );
+    }
+
+    #[test]
+    fn removes_computed_fields_after_sorting() {
+        let order =
+            ReviewOrder::new(Some(vec![order_input(ReviewOrderField::MostHelpful, None)])).unwrap();
+        let stages = order.sort_stages();
+        assert_eq!(stages.len(), 3);
+        assert!(stages[0]
+            .get_document("$addFields")
+            .unwrap()
+            .contains_key("helpfulness_score"));
+        assert_eq!(
+            stages[1].get_document("$sort").unwrap(),
+            &doc! {"helpfulness_score": -1, "_id": 1}
+        );
+        assert_eq!(
+            stages[2].get_document("$project").unwrap(),
+            &doc! {"helpfulness_score": 0}
+        );
+    }
+
+    #[test]
+    fn sorts_stored_fields_without_computing() {
+        let order = ReviewOrder::new(Some(vec![order_input(
+            ReviewOrderField::UpdatedAt,
+            Some(OrderDirection::Desc),
+        )]))
+        .unwrap();
+        assert_eq!(
+            order.sort_stages(),
+            vec![doc! {"$sort": {"last_updated_at": -1, "_id": 1}}]
+        );
+    }
+
+    #[test]
+    fn detects_ordering_by_user_id_as_tiebreaker() {
+        let order = ReviewOrder::new(Some(vec![
+            order_input(ReviewOrderField::Rating, None),
+            order_input(ReviewOrderField::UserId, None),
+        ]))
+        .unwrap();
+        assert!(order.orders_by(ReviewOrderField::UserId));
+        assert!(!ReviewOrder::new(None)
+            .unwrap()
+            .orders_by(ReviewOrderField::UserId));
+    }
+}
@@ -1,17 +1,22 @@
-use async_graphql::{ComplexObject, Context, Error, Result, SimpleObject};
-use bson::{doc, Bson, Document, Uuid};
-use mongodb::{options::FindOptions, Collection, Database};
-use mongodb_cursor_pagination::{error::CursorError, FindResult, PaginatedCursor};
+use async_graphql::{dataloader::DataLoader, ComplexObject, Context, Error, Result, SimpleObject};
+use bson::{doc, Bson, DateTime, Uuid};
+use mongodb::Database;
 use serde::{Deserialize, Serialize};
 
-use super::{
-    connection::{
-        base_connection::{BaseConnection, FindResultWrapper},
-        review_connection::ReviewConnection,
+use crate::graphql::{
+    loader::{
+        rating_summary_loader::load_rating_summary,
+        review_connection_loader::{ReviewConnectionKey, ReviewConnectionLoader, ReviewPage},
+        ReviewParent,
     },
+    query_limits::{connection_complexity, RATING_SUMMARY_COMPLEXITY},
+    response_cache::RESPONSE_CACHE,
+};
+
+use super::{
+    connection::review_connection::ReviewConnection,
     order_datatypes::ReviewOrderInput,
-    product_variant::calculate_average_rating,
-    review::Review,
+    rating_trend::{calculate_rating_trend, RatingTrendBucket, RatingTrendGranularity},
 };
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Copy, Clone, SimpleObject)]
@@ -24,48 +29,76 @@ pub struct Product {
 #[ComplexObject]
 impl Product {
     /// Retrieves reviews of product.
+    ///
+    /// Hidden reviews are only retrieved for their author and users with the `review:moderate` permission.
+    #[graphql(
+        complexity = "connection_complexity(first, child_complexity)",
+        cache_control(max_age = 60)
+    )]
     async fn reviews<'a>(
         &self,
         ctx: &Context<'a>,
-        #[graphql(desc = "Describes that the `first` N reviews should be retrieved.")]
+        #[graphql(
+            desc = "Describes that the `first` N reviews should be retrieved, by default 20 and at most 100."
+        )]
         first: Option<u32>,
         #[graphql(desc = "Describes how many reviews should be skipped at the beginning.")]
         skip: Option<u64>,
-        #[graphql(desc = "Specifies the order in which reviews are retrieved.")] order_by: Option<
-            ReviewOrderInput,
-        >,
+        #[graphql(
+            desc = "Specifies the order in which reviews are retrieved, later order inputs break ties of earlier ones."
+        )]
+        order_by: Option<Vec<ReviewOrderInput>>,
     ) -> Result<ReviewConnection> {
-        let db_client = ctx.data::<Database>()?;
-        let collection: Collection<Review> = db_client.collection::<Review>("reviews");
-        let review_order = order_by.unwrap_or_default();
-        let sorting_doc = doc! {review_order.field.unwrap_or_default().as_str(): i32::from(review_order.direction.unwrap_or_default())};
-        let find_options = FindOptions::builder()
-            .skip(skip)
-            .limit(first.map(|definitely_first| i64::from(definitely_first)))
-            .sort(sorting_doc)
-            .build();
-        let document_collection = collection.clone_with_type::<Document>();
-        let filter = doc! {"product_variant.product_id": self._id};
-        let maybe_find_results: Result<FindResult<Review>, CursorError> =
-            PaginatedCursor::new(Some(find_options.clone()), None, None)
-                .find(&document_collection, Some(&filter))
-                .await;
-        match maybe_find_results {
-            Ok(find_results) => {
-                let find_result_wrapper = FindResultWrapper(find_results);
-                let connection = Into::<BaseConnection<Review>>::into(find_result_wrapper);
-                Ok(Into::<ReviewConnection>::into(connection))
-            }
-            Err(_) => return Err(Error::new("Retrieving reviews failed in MongoDB.")),
-        }
+        let page = ReviewPage::new(ctx, first, skip, order_by)?;
+        let key = ReviewConnectionKey {
+            parent: ReviewParent::Product(self._id),
+            page,
+        };
+        let review_connection_loader = ctx.data::<DataLoader<ReviewConnectionLoader>>()?;
+        RESPONSE_CACHE
+            .review_connection(&key, async {
+                review_connection_loader
+                    .load_one(key.clone())
+                    .await?
+                    .ok_or_else(|| Error::new("Retrieving reviews failed in MongoDB."))
+            })
+            .await
     }
 
     /// Retrieves average rating of product.
-    async fn average_rating<'a>(&self, ctx: &Context<'a>) -> Option<f32> {
-        match self.reviews(ctx, None, None, None).await {
-            Ok(review_connection) => calculate_average_rating(review_connection).await,
-            Err(_) => None,
-        }
+    #[graphql(complexity = "RATING_SUMMARY_COMPLEXITY", cache_control(max_age = 60))]
+    async fn average_rating<'a>(&self, ctx: &Context<'a>) -> Result<Option<f32>> {
+        let rating_summary = load_rating_summary(ctx, ReviewParent::Product(self._id)).await?;
+        Ok(rating_summary
+            .average_rating
+            .map(|average_rating| average_rating as f32))
+    }
+
+    /// Retrieves number of visible reviews of product.
+    #[graphql(complexity = "RATING_SUMMARY_COMPLEXITY", cache_control(max_age = 60))]
+    async fn review_count<'a>(&self, ctx: &Context<'a>) -> Result<u64> {
+        let rating_summary = load_rating_summary(ctx, ReviewParent::Product(self._id)).await?;
+        Ok(rating_summary.review_count)
+    }
+
+    /// Retrieves number of visible reviews and average rating of product per day, week or month,
+    /// e.g. to see whether ratings improve after a fix.
+    #[graphql(complexity = "RATING_SUMMARY_COMPLEXITY", cache_control(max_age = 60))]
+    async fn rating_trend<'a>(
+        &self,
+        ctx: &Context<'a>,
+        #[graphql(desc = "Length of the time buckets reviews are grouped into by creation.")]
+        granularity: RatingTrendGranularity,
+        #[graphql(desc = "Only includes reviews created at or after this timestamp.")] from: Option<
+            DateTime,
+        >,
+        #[graphql(desc = "Only includes reviews created before this timestamp.")] to: Option<
+            DateTime,
+        >,
+    ) -> Result<Vec<RatingTrendBucket>> {
+        let db_client = ctx.data::<Database>()?;
+        let parent = ReviewParent::Product(self._id);
+        calculate_rating_trend(db_client, parent, granularity, from, to).await
     }
 }
 
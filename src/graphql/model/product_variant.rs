@@ -1,21 +1,29 @@
-use async_graphql::{ComplexObject, Context, Error, Result, SimpleObject};
-use bson::{doc, Bson, Document, Uuid};
-use mongodb::{options::FindOptions, Collection, Database};
-use mongodb_cursor_pagination::{error::CursorError, FindResult, PaginatedCursor};
+use async_graphql::{dataloader::DataLoader, ComplexObject, Context, Error, Result, SimpleObject};
+use bson::{doc, Bson, DateTime, Uuid};
+use mongodb::Database;
 use serde::{Deserialize, Serialize};
 
 use crate::event::http_event_service::ProductVariantEventData;
+use crate::graphql::{
+    loader::{
+        rating_summary_loader::load_rating_summary,
+        review_connection_loader::{ReviewConnectionKey, ReviewConnectionLoader, ReviewPage},
+        ReviewParent,
+    },
+    query_limits::{connection_complexity, RATING_SUMMARY_COMPLEXITY},
+    response_cache::RESPONSE_CACHE,
+};
 
 use super::{
-    connection::{
-        base_connection::{BaseConnection, FindResultWrapper},
-        review_connection::ReviewConnection,
-    },
+    connection::review_connection::ReviewConnection,
     order_datatypes::ReviewOrderInput,
-    review::Review,
+    rating_trend::{calculate_rating_trend, RatingTrendBucket, RatingTrendGranularity},
 };
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Copy, Clone, SimpleObject)]
+/// Local projection of a product variant, mirrored from catalog events.
+///
+/// The mirrored metadata is owned by the catalog service and therefore not exposed by this subgraph.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, SimpleObject)]
 #[graphql(complex)]
 pub struct ProductVariant {
     /// Product variant UUID.
@@ -23,93 +31,130 @@ pub struct ProductVariant {
     /// Associated product UUID.
     #[graphql(skip)]
     pub product_id: Uuid,
+    /// Name of the product variant.
+    #[serde(default)]
+    #[graphql(skip)]
+    pub name: Option<String>,
+    /// Stock keeping unit of the product variant.
+    #[serde(default)]
+    #[graphql(skip)]
+    pub sku: Option<String>,
+    /// Flag if product variant is active, only active product variants can be reviewed.
+    #[serde(default = "default_is_active")]
+    #[graphql(skip)]
+    pub is_active: bool,
+    /// Category of the associated product.
+    #[serde(default)]
+    #[graphql(skip)]
+    pub product_category: Option<String>,
 }
 
 #[ComplexObject]
 impl ProductVariant {
     /// Retrieves reviews of product variant.
+    ///
+    /// Hidden reviews are only retrieved for their author and users with the `review:moderate` permission.
     // TODO reviews should be optional
+    #[graphql(
+        complexity = "connection_complexity(first, child_complexity)",
+        cache_control(max_age = 60)
+    )]
     async fn reviews<'a>(
         &self,
         ctx: &Context<'a>,
-        #[graphql(desc = "Describes that the `first` N reviews should be retrieved.")]
+        #[graphql(
+            desc = "Describes that the `first` N reviews should be retrieved, by default 20 and at most 100."
+        )]
         first: Option<u32>,
         #[graphql(desc = "Describes how many reviews should be skipped at the beginning.")]
         skip: Option<u64>,
-        #[graphql(desc = "Specifies the order in which reviews are retrieved.")] order_by: Option<
-            ReviewOrderInput,
-        >,
+        #[graphql(
+            desc = "Specifies the order in which reviews are retrieved, later order inputs break ties of earlier ones."
+        )]
+        order_by: Option<Vec<ReviewOrderInput>>,
     ) -> Result<ReviewConnection> {
-        let db_client = ctx.data::<Database>()?;
-        let collection: Collection<Review> = db_client.collection::<Review>("reviews");
-        let review_order = order_by.unwrap_or_default();
-        let sorting_doc = doc! {review_order.field.unwrap_or_default().as_str(): i32::from(review_order.direction.unwrap_or_default())};
-        let find_options = FindOptions::builder()
-            .skip(skip)
-            .limit(first.map(|definitely_first| i64::from(definitely_first)))
-            .sort(sorting_doc)
-            .build();
-        let document_collection = collection.clone_with_type::<Document>();
-        let filter = doc! {"product_variant._id": self._id};
-        let maybe_find_results: Result<FindResult<Review>, CursorError> =
-            PaginatedCursor::new(Some(find_options.clone()), None, None)
-                .find(&document_collection, Some(&filter))
-                .await;
-        match maybe_find_results {
-            Ok(find_results) => {
-                let find_result_wrapper = FindResultWrapper(find_results);
-                let connection = Into::<BaseConnection<Review>>::into(find_result_wrapper);
-                Ok(Into::<ReviewConnection>::into(connection))
-            }
-            Err(_) => return Err(Error::new("Retrieving reviews failed in MongoDB.")),
-        }
+        let page = ReviewPage::new(ctx, first, skip, order_by)?;
+        let key = ReviewConnectionKey {
+            parent: ReviewParent::ProductVariant(self._id),
+            page,
+        };
+        let review_connection_loader = ctx.data::<DataLoader<ReviewConnectionLoader>>()?;
+        RESPONSE_CACHE
+            .review_connection(&key, async {
+                review_connection_loader
+                    .load_one(key.clone())
+                    .await?
+                    .ok_or_else(|| Error::new("Retrieving reviews failed in MongoDB."))
+            })
+            .await
     }
 
     /// Retrieves average rating of product variant.
-    async fn average_rating<'a>(&self, ctx: &Context<'a>) -> Option<f32> {
-        match self.reviews(ctx, None, None, None).await {
-            Ok(review_connection) => calculate_average_rating(review_connection).await,
-            Err(_) => None,
-        }
+    #[graphql(complexity = "RATING_SUMMARY_COMPLEXITY", cache_control(max_age = 60))]
+    async fn average_rating<'a>(&self, ctx: &Context<'a>) -> Result<Option<f32>> {
+        let rating_summary =
+            load_rating_summary(ctx, ReviewParent::ProductVariant(self._id)).await?;
+        Ok(rating_summary
+            .average_rating
+            .map(|average_rating| average_rating as f32))
+    }
+
+    /// Retrieves number of visible reviews of product variant.
+    #[graphql(complexity = "RATING_SUMMARY_COMPLEXITY", cache_control(max_age = 60))]
+    async fn review_count<'a>(&self, ctx: &Context<'a>) -> Result<u64> {
+        let rating_summary =
+            load_rating_summary(ctx, ReviewParent::ProductVariant(self._id)).await?;
+        Ok(rating_summary.review_count)
+    }
+
+    /// Retrieves number of visible reviews and average rating of product variant per day, week or month,
+    /// e.g. to see whether ratings improve after a fix.
+    #[graphql(complexity = "RATING_SUMMARY_COMPLEXITY", cache_control(max_age = 60))]
+    async fn rating_trend<'a>(
+        &self,
+        ctx: &Context<'a>,
+        #[graphql(desc = "Length of the time buckets reviews are grouped into by creation.")]
+        granularity: RatingTrendGranularity,
+        #[graphql(desc = "Only includes reviews created at or after this timestamp.")] from: Option<
+            DateTime,
+        >,
+        #[graphql(desc = "Only includes reviews created before this timestamp.")] to: Option<
+            DateTime,
+        >,
+    ) -> Result<Vec<RatingTrendBucket>> {
+        let db_client = ctx.data::<Database>()?;
+        let parent = ReviewParent::ProductVariant(self._id);
+        calculate_rating_trend(db_client, parent, granularity, from, to).await
     }
 }
 
 impl From<ProductVariant> for Bson {
     fn from(value: ProductVariant) -> Self {
-        Bson::Document(doc!("_id": value._id, "product_id": value.product_id))
+        Bson::Document(doc!(
+            "_id": value._id,
+            "product_id": value.product_id,
+            "name": value.name,
+            "sku": value.sku,
+            "is_active": value.is_active,
+            "product_category": value.product_category,
+        ))
     }
 }
 
-impl From<ProductVariantEventData> for ProductVariant {
-    fn from(value: ProductVariantEventData) -> Self {
+impl From<&ProductVariantEventData> for ProductVariant {
+    fn from(value: &ProductVariantEventData) -> Self {
         Self {
             _id: value.id,
             product_id: value.product_id,
+            name: value.name.clone(),
+            sku: value.sku.clone(),
+            is_active: value.is_active.unwrap_or_else(default_is_active),
+            product_category: value.product_category.clone(),
         }
     }
 }
 
-/// Shared function to calculate average rating of a review connection.
-///
-/// Filters reviews with `is_visible == false` to exclude them from the average rating.
-///
-/// `review_connection` - Connection of reviews to calculate average rating for.
-pub async fn calculate_average_rating<'a>(review_connection: ReviewConnection) -> Option<f32> {
-    let reviews = review_connection.nodes.clone();
-    let (accumulated_reviews, total_count) =
-        reviews.iter().filter(|review| review.is_visible).fold(
-            (0, 0),
-            |(prev_accumulated_reviews, prev_total_count), review| {
-                (
-                    prev_accumulated_reviews + review.rating as i32,
-                    prev_total_count + 1,
-                )
-            },
-        );
-    if total_count == 0 {
-        None
-    } else {
-        let average_rating = accumulated_reviews as f32 / total_count as f32;
-        Some(average_rating)
-    }
+/// Product variants mirrored before metadata was stored are considered active.
+fn default_is_active() -> bool {
+    true
 }
@@ -0,0 +1,125 @@
+use async_graphql::{Enum, Error, Result, SimpleObject};
+use bson::{doc, DateTime, Document};
+use futures::TryStreamExt;
+use mongodb::Database;
+use serde::Deserialize;
+
+use crate::graphql::loader::ReviewParent;
+
+use super::review::rating_value_expression;
+
+/// Maximum number of buckets of a rating trend, only the most recent buckets are retrieved.
+const MAX_RATING_TREND_BUCKETS: i64 = 366;
+
+/// Length of the time buckets of a rating trend.
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum RatingTrendGranularity {
+    /// Buckets of one day.
+    Day,
+    /// Buckets of one week, starting on Monday.
+    Week,
+    /// Buckets of one month.
+    Month,
+}
+
+impl RatingTrendGranularity {
+    /// Returns the unit of the MongoDB `$dateTrunc` operator.
+    fn as_str(&self) -> &'static str {
+        match self {
+            RatingTrendGranularity::Day => "day",
+            RatingTrendGranularity::Week => "week",
+            RatingTrendGranularity::Month => "month",
+        }
+    }
+}
+
+/// Number of reviews and average rating of the visible reviews created in a time bucket.
+#[derive(Debug, Clone, SimpleObject)]
+pub struct RatingTrendBucket {
+    /// Start of the time bucket in UTC.
+    pub start: DateTime,
+    /// Number of visible reviews created in the time bucket.
+    pub review_count: u64,
+    /// Average rating of visible reviews created in the time bucket.
+    pub average_rating: Option<f32>,
+}
+
+/// Rating trend bucket as grouped by MongoDB.
+#[derive(Deserialize)]
+struct RatingTrendGroup {
+    _id: DateTime,
+    review_count: u64,
+    average_rating: Option<f64>,
+}
+
+/// Calculates the rating trend of a parent, bucketing its visible reviews by `created_at`.
+///
+/// Buckets without reviews are omitted, at most `MAX_RATING_TREND_BUCKETS` buckets are returned in chronological order.
+///
+/// * `db_client` - MongoDB database client.
+/// * `parent` - Product or product variant the rating trend is calculated for.
+/// * `granularity` - Length of the time buckets.
+/// * `from` - Only includes reviews created at or after this timestamp if set.
+/// * `to` - Only includes reviews created before this timestamp if set.
+pub async fn calculate_rating_trend(
+    db_client: &Database,
+    parent: ReviewParent,
+    granularity: RatingTrendGranularity,
+    from: Option<DateTime>,
+    to: Option<DateTime>,
+) -> Result<Vec<RatingTrendBucket>> {
+    if let (Some(definitely_from), Some(definitely_to)) = (from, to)
+        && definitely_from >= definitely_to
+    {
+        return Err(Error::new("Rating trend: `from` must be before `to`."));
+    }
+    let mut created_at_filter = Document::new();
+    if let Some(definitely_from) = from {
+        created_at_filter.insert("$gte", definitely_from);
+    }
+    if let Some(definitely_to) = to {
+        created_at_filter.insert("$lt", definitely_to);
+    }
+    let mut filter = doc! {parent.field(): parent.id(), "is_visible": true};
+    if !created_at_filter.is_empty() {
+        filter.insert("created_at", created_at_filter);
+    }
+    let mut bucket_start = doc! {"date": "$created_at", "unit": granularity.as_str()};
+    if granularity == RatingTrendGranularity::Week {
+        bucket_start.insert("startOfWeek", "monday");
+    }
+    let pipeline = vec![
+        doc! {"$match": filter},
+        doc! {"$group": {
+            "_id": {"$dateTrunc": bucket_start},
+            "review_count": {"$sum": 1},
+            "average_rating": {"$avg": rating_value_expression()},
+        }},
+        doc! {"$sort": {"_id": -1}},
+        doc! {"$limit": MAX_RATING_TREND_BUCKETS},
+    ];
+    let collection = db_client.collection::<Document>("reviews");
+    let maybe_documents: mongodb::error::Result<Vec<Document>> =
+        match collection.aggregate(pipeline, None).await {
+            Ok(cursor) => cursor.try_collect().await,
+            Err(error) => Err(error),
+        };
+    let documents =
+        maybe_documents.map_err(|_| Error::new("Calculating rating trend failed in MongoDB."))?;
+    let mut rating_trend = documents
+        .into_iter()
+        .map(|document| {
+            let group: RatingTrendGroup = bson::from_document(document)
+                .map_err(|_| Error::new("Calculating rating trend failed in MongoDB."))?;
+            Ok(RatingTrendBucket {
+                start: group._id,
+                review_count: group.review_count,
+                average_rating: group
+                    .average_rating
+                    .map(|average_rating| average_rating as f32),
+            })
+        })
+        .collect::<Result<Vec<RatingTrendBucket>>>()?;
+    rating_trend.reverse();
+    Ok(rating_trend)
+}
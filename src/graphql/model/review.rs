@@ -1,17 +1,26 @@
-use async_graphql::{Enum, SimpleObject};
+use std::{fmt, str::FromStr};
+
+use async_graphql::{ComplexObject, Context, Enum, SimpleObject};
 use bson::{datetime::DateTime, Bson};
-use bson::{doc, Uuid};
+use bson::{doc, Document, Uuid};
+use mongodb::{Collection, IndexModel};
 use serde::{Deserialize, Serialize};
 
+use crate::authorization::{
+    is_authorized, permission::Permission, AuthorizedUserHeader, UserGuard,
+};
+
 use super::product_variant::ProductVariant;
 use super::user::User;
 
 /// The review of a user.
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone, SimpleObject)]
+#[graphql(complex)]
 pub struct Review {
     /// review UUID.
     pub _id: Uuid,
     /// User.
+    #[graphql(skip)]
     pub user: User,
     /// Product variant that review is about.
     pub product_variant: ProductVariant,
@@ -25,27 +34,298 @@ pub struct Review {
     pub last_updated_at: DateTime,
     /// Flag if review is visible,
     pub is_visible: bool,
+    /// Flag if review is displayed without its author.
+    #[serde(default)]
+    pub is_anonymous: bool,
+    /// Number of users who voted the review helpful, see `voteReview`.
+    #[serde(default)]
+    pub helpful_vote_count: u32,
+    /// Number of users who voted the review unhelpful.
+    #[serde(default)]
+    pub unhelpful_vote_count: u32,
+    /// Flag if the user purchased the product variant in review, only set by users with the `review:write:any` permission.
+    #[serde(default)]
+    pub is_verified_purchase: bool,
+    /// URLs of images and videos attached to review.
+    #[serde(default)]
+    pub media_urls: Vec<String>,
+}
+
+#[ComplexObject]
+impl Review {
+    /// User, only resolved for anonymous reviews if requested by the author or a user with the `review:moderate` permission.
+    ///
+    /// Nullable, so that a failing guard does not discard the whole review.
+    #[graphql(
+        guard = "UserGuard::new(self.user._id, Permission::ModerateReviews).when(self.is_anonymous)"
+    )]
+    async fn user(&self) -> Option<User> {
+        Some(self.user.clone())
+    }
+}
+
+impl Review {
+    /// Checks if the review is visible to the user of a context.
+    ///
+    /// Hidden reviews are only visible to their author and users with the `review:moderate` permission.
+    ///
+    /// * `ctx` - GraphQL context which may contain the `Authorized-User` header.
+    pub fn is_visible_to(&self, ctx: &Context) -> bool {
+        self.is_visible || is_authorized(ctx, Some(self.user._id), Permission::ModerateReviews)
+    }
+}
+
+/// Reviews visible to the user of a context.
+///
+/// Used instead of the context where visibility is part of a key, e.g. of a DataLoader.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReviewVisibility {
+    /// All reviews, visible to users with the `review:moderate` permission.
+    All,
+    /// Visible reviews and hidden reviews of the authorized user.
+    VisibleOrAuthoredBy(Uuid),
+    /// Only visible reviews, if no user is authorized.
+    VisibleOnly,
+}
+
+impl ReviewVisibility {
+    /// Determines the reviews visible to the user of a context.
+    ///
+    /// * `ctx` - GraphQL context which may contain the `Authorized-User` header.
+    pub fn of(ctx: &Context) -> Self {
+        if is_authorized(ctx, None, Permission::ModerateReviews) {
+            return Self::All;
+        }
+        match ctx.data_opt::<AuthorizedUserHeader>() {
+            Some(authorized_user_header) => Self::VisibleOrAuthoredBy(authorized_user_header.id),
+            None => Self::VisibleOnly,
+        }
+    }
+
+    /// Restricts a MongoDB review filter to the visible reviews.
+    ///
+    /// Only narrows the filter, conditions of the filter are kept.
+    ///
+    /// * `filter` - MongoDB filter of reviews to restrict.
+    pub fn restrict(&self, filter: &mut Document) {
+        match self {
+            Self::All => {}
+            Self::VisibleOrAuthoredBy(id) => {
+                let condition = doc! {"$or": [{"is_visible": true}, {"user._id": *id}]};
+                match filter.get_array_mut("$and") {
+                    Ok(conditions) => conditions.push(Bson::Document(condition)),
+                    Err(_) => {
+                        filter.insert("$and", vec![condition]);
+                    }
+                }
+            }
+            Self::VisibleOnly => {
+                filter.insert("is_visible", true);
+            }
+        }
+    }
 }
 
+#[allow(clippy::enum_variant_names)]
 #[derive(Enum, Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum Rating {
     OneStars = 1,
     TwoStars = 2,
     ThreeStars = 3,
+    /// Also read from `FourStarst`, which rating updates stored before the name was corrected.
+    #[serde(alias = "FourStarst")]
     FourStars = 4,
     FiveStars = 5,
 }
 
 impl Rating {
-    /// Converts enum value to string.
-    pub fn to_string(&self) -> String {
-        match self {
-            Rating::OneStars => "OneStars".to_string(),
-            Rating::TwoStars => "TwoStars".to_string(),
-            Rating::ThreeStars => "ThreeStars".to_string(),
-            Rating::FourStars => "FourStarst".to_string(),
-            Rating::FiveStars => "FiveStars".to_string(),
+    /// All ratings in ascending order.
+    pub const ALL: [Rating; 5] = [
+        Rating::OneStars,
+        Rating::TwoStars,
+        Rating::ThreeStars,
+        Rating::FourStars,
+        Rating::FiveStars,
+    ];
+}
+
+/// Maximum number of media URLs attached to a review.
+pub const MAX_MEDIA_URLS: usize = 10;
+
+/// Checks that media URLs attached to a review are at most `MAX_MEDIA_URLS` absolute HTTP(S) URLs.
+///
+/// * `media_urls` - URLs of images and videos attached to a review.
+pub fn validate_media_urls(media_urls: &[String]) -> Result<(), String> {
+    if media_urls.len() > MAX_MEDIA_URLS {
+        return Err(format!(
+            "A review can have at most {} media URLs, got {}.",
+            MAX_MEDIA_URLS,
+            media_urls.len()
+        ));
+    }
+    match media_urls.iter().find(|media_url| !is_http_url(media_url)) {
+        Some(media_url) => Err(format!(
+            "Media URL: `{}` is not an absolute HTTP(S) URL.",
+            media_url
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Checks if a URL is an absolute HTTP(S) URL with a host and without whitespace.
+///
+/// * `url` - URL to check.
+fn is_http_url(url: &str) -> bool {
+    let maybe_rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"));
+    match maybe_rest {
+        Some(rest) => {
+            !rest.is_empty() && !rest.starts_with('/') && !url.chars().any(char::is_whitespace)
         }
+        None => false,
+    }
+}
+
+/// Misspelled name of `FourStars` stored by rating updates before the name was corrected.
+const LEGACY_FOUR_STARS: &str = "FourStarst";
+
+/// Rewrites ratings stored under the misspelled name `FourStarst` to `FourStars`,
+/// so that filters and aggregations on the `rating` field match them.
+///
+/// Returns the number of migrated reviews.
+///
+/// * `collection` - MongoDB collection of reviews.
+pub async fn migrate_legacy_ratings(
+    collection: &Collection<Document>,
+) -> mongodb::error::Result<u64> {
+    let result = collection
+        .update_many(
+            doc! {"rating": LEGACY_FOUR_STARS},
+            doc! {"$set": {"rating": Rating::FourStars}},
+            None,
+        )
+        .await?;
+    Ok(result.modified_count)
+}
+
+/// Creates the indexes of the fields referencing the user, product and product variant of a review,
+/// used to load and summarize the reviews of an entity.
+///
+/// * `collection` - MongoDB collection of reviews.
+pub async fn create_review_parent_indexes(
+    collection: &Collection<Review>,
+) -> mongodb::error::Result<()> {
+    let indexes = [
+        "user._id",
+        "product_variant.product_id",
+        "product_variant._id",
+    ]
+    .into_iter()
+    .map(|field| IndexModel::builder().keys(doc! {field: 1}).build());
+    collection.create_indexes(indexes, None).await.map(|_| ())
+}
+
+/// MongoDB aggregation expression converting the stored name of the `rating` field to its number of stars.
+pub fn rating_value_expression() -> Document {
+    let branches: Vec<Document> = Rating::ALL
+        .iter()
+        .map(|rating| doc! {"case": {"$eq": ["$rating", *rating]}, "then": *rating as i32})
+        .collect();
+    doc! {"$switch": {"branches": branches, "default": Bson::Null}}
+}
+
+/// z-score of the 95% confidence level of the Wilson score interval of helpful votes.
+const WILSON_Z: f64 = 1.96;
+
+/// Days after which the recency of a review only contributes half to its relevance.
+const RELEVANCE_HALF_LIFE_DAYS: f64 = 90.0;
+
+/// Number of body characters from which a review gets the full length weight.
+const RELEVANCE_FULL_BODY_LENGTH: i32 = 500;
+
+/// Number of attached media from which a review gets the full media weight.
+const RELEVANCE_FULL_MEDIA_COUNT: i32 = 3;
+
+/// Weights of recency, body length, verified purchase and attached media in the relevance of a review, summing up to 1.
+const RELEVANCE_WEIGHTS: (f64, f64, f64, f64) = (0.4, 0.2, 0.25, 0.15);
+
+/// MongoDB aggregation expression computing the lower bound of the Wilson score interval of the helpful votes of a review.
+///
+/// Ranks reviews with many mostly helpful votes above reviews with few votes, reviews without votes score 0.
+pub fn helpfulness_score_expression() -> Document {
+    let z_squared = WILSON_Z * WILSON_Z;
+    let wilson_lower_bound = doc! {"$divide": [
+        {"$subtract": [
+            {"$add": ["$$share", {"$divide": [z_squared, {"$multiply": [2, "$$total"]}]}]},
+            {"$multiply": [WILSON_Z, {"$sqrt": {"$divide": [
+                {"$add": [
+                    {"$multiply": ["$$share", {"$subtract": [1, "$$share"]}]},
+                    {"$divide": [z_squared, {"$multiply": [4, "$$total"]}]},
+                ]},
+                "$$total",
+            ]}}]},
+        ]},
+        {"$add": [1, {"$divide": [z_squared, "$$total"]}]},
+    ]};
+    doc! {"$let": {
+        "vars": {
+            "helpful": {"$ifNull": ["$helpful_vote_count", 0]},
+            "total": {"$add": [
+                {"$ifNull": ["$helpful_vote_count", 0]},
+                {"$ifNull": ["$unhelpful_vote_count", 0]},
+            ]},
+        },
+        "in": {"$cond": [
+            {"$eq": ["$$total", 0]},
+            0.0,
+            {"$let": {
+                "vars": {"share": {"$divide": ["$$helpful", "$$total"]}},
+                "in": wilson_lower_bound,
+            }},
+        ]},
+    }}
+}
+
+/// MongoDB aggregation expression computing the relevance of a review between 0 and 1.
+///
+/// Weights the recency of the review, decaying exponentially with its age, the length of its body,
+/// whether the user purchased the product variant and the number of attached media.
+pub fn relevance_score_expression() -> Document {
+    let (recency_weight, length_weight, verified_purchase_weight, media_weight) = RELEVANCE_WEIGHTS;
+    let age_in_days = doc! {"$divide": [{"$subtract": ["$$NOW", "$created_at"]}, 86_400_000]};
+    let recency = doc! {"$exp": {"$multiply": [
+        -std::f64::consts::LN_2 / RELEVANCE_HALF_LIFE_DAYS,
+        {"$max": [age_in_days, 0]},
+    ]}};
+    let length = doc! {"$min": [
+        {"$divide": [{"$strLenCP": "$body"}, RELEVANCE_FULL_BODY_LENGTH]},
+        1,
+    ]};
+    let verified_purchase = doc! {"$cond": [{"$eq": ["$is_verified_purchase", true]}, 1, 0]};
+    let media = doc! {"$min": [
+        {"$divide": [{"$size": {"$ifNull": ["$media_urls", []]}}, RELEVANCE_FULL_MEDIA_COUNT]},
+        1,
+    ]};
+    doc! {"$add": [
+        {"$multiply": [recency_weight, recency]},
+        {"$multiply": [length_weight, length]},
+        {"$multiply": [verified_purchase_weight, verified_purchase]},
+        {"$multiply": [media_weight, media]},
+    ]}
+}
+
+/// Converts enum value to string.
+impl fmt::Display for Rating {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rating = match self {
+            Rating::OneStars => "OneStars",
+            Rating::TwoStars => "TwoStars",
+            Rating::ThreeStars => "ThreeStars",
+            Rating::FourStars => "FourStars",
+            Rating::FiveStars => "FiveStars",
+        };
+        write!(f, "{}", rating)
     }
 }
 
@@ -54,3 +334,52 @@ impl From<Rating> for Bson {
         Bson::String(value.to_string())
     }
 }
+
+/// Parses a rating from its name, e.g. `FourStars`, or its number of stars, e.g. `4`.
+impl FromStr for Rating {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim() {
+            "OneStars" | "1" => Ok(Rating::OneStars),
+            "TwoStars" | "2" => Ok(Rating::TwoStars),
+            "ThreeStars" | "3" => Ok(Rating::ThreeStars),
+            "FourStars" | "4" => Ok(Rating::FourStars),
+            "FiveStars" | "5" => Ok(Rating::FiveStars),
+            _ => Err(format!("Rating: `{}` is not a valid rating.", value)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_absolute_http_media_urls() {
+        let media_urls = vec![
+            "https://cdn.example.com/review/1.jpg".to_string(),
+            "http://cdn.example.com/review/2.mp4".to_string(),
+        ];
+        assert_eq!(validate_media_urls(&media_urls), Ok(()));
+    }
+
+    #[test]
+    fn rejects_invalid_media_urls() {
+        for media_url in [
+            "ftp://cdn.example.com/1.jpg",
+            "https://",
+            "https:///1.jpg",
+            "https://cdn.example.com/a b.jpg",
+            "/review/1.jpg",
+        ] {
+            assert!(validate_media_urls(&[media_url.to_string()]).is_err());
+        }
+    }
+
+    #[test]
+    fn rejects_too_many_media_urls() {
+        let media_urls = vec!["https://cdn.example.com/1.jpg".to_string(); MAX_MEDIA_URLS + 1];
+        assert!(validate_media_urls(&media_urls).is_err());
+    }
+}
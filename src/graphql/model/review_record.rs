@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+
+use super::review::Review;
+
+/// Flat representation of a review in import and export files.
+///
+/// UUIDs, ratings and timestamps are kept as strings, so that invalid values can be reported per record.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReviewRecord {
+    /// Review UUID, generated on import if empty.
+    #[serde(default)]
+    pub id: Option<String>,
+    /// UUID of user owning the review.
+    pub user_id: String,
+    /// UUID of product variant in review.
+    pub product_variant_id: String,
+    /// Body of review.
+    pub body: String,
+    /// Rating of review, e.g. `FourStars` or `4`.
+    pub rating: String,
+    /// Timestamp when review was created in RFC 3339 format, set to the import time if empty.
+    #[serde(default)]
+    pub created_at: Option<String>,
+    /// Timestamp when review was last updated in RFC 3339 format, set to `created_at` if empty.
+    #[serde(default)]
+    pub last_updated_at: Option<String>,
+    /// Flag if review is visible, by default set to true.
+    #[serde(default)]
+    pub is_visible: Option<bool>,
+    /// Flag if review is displayed without its author, by default set to false.
+    #[serde(default)]
+    pub is_anonymous: Option<bool>,
+    /// Number of users who voted the review helpful, only exported.
+    ///
+    /// Ignored on import, since the votes behind the count are not imported.
+    #[serde(default)]
+    pub helpful_vote_count: Option<u32>,
+    /// Number of users who voted the review unhelpful, only exported.
+    ///
+    /// Ignored on import, since the votes behind the count are not imported.
+    #[serde(default)]
+    pub unhelpful_vote_count: Option<u32>,
+    /// Flag if the user purchased the product variant in review, by default set to false.
+    #[serde(default)]
+    pub is_verified_purchase: Option<bool>,
+    /// URLs of images and videos attached to review separated by spaces, by default none.
+    #[serde(default)]
+    pub media_urls: Option<String>,
+}
+
+impl From<&Review> for ReviewRecord {
+    fn from(value: &Review) -> Self {
+        Self {
+            id: Some(value._id.to_string()),
+            user_id: value.user._id.to_string(),
+            product_variant_id: value.product_variant._id.to_string(),
+            body: value.body.clone(),
+            rating: value.rating.to_string(),
+            created_at: value.created_at.try_to_rfc3339_string().ok(),
+            last_updated_at: value.last_updated_at.try_to_rfc3339_string().ok(),
+            is_visible: Some(value.is_visible),
+            is_anonymous: Some(value.is_anonymous),
+            helpful_vote_count: Some(value.helpful_vote_count),
+            unhelpful_vote_count: Some(value.unhelpful_vote_count),
+            is_verified_purchase: Some(value.is_verified_purchase),
+            media_urls: Some(value.media_urls.join(" ")),
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use bson::{doc, DateTime, Uuid};
+use futures::TryStreamExt;
+use mongodb::{options::UpdateOptions, Collection, IndexModel};
+use serde::{Deserialize, Serialize};
+
+use super::review::Review;
+
+/// Vote of a user on whether a review is helpful.
+///
+/// Each user has at most one vote per review, the vote counts of reviews are the number of these votes.
+/// The vote counts are recounted from the votes after each change, so that a failed count update
+/// is corrected by the next vote on the review.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReviewVote {
+    /// Review and user of the vote.
+    pub _id: ReviewVoteId,
+    /// Flag if the user voted the review helpful, otherwise unhelpful.
+    pub is_helpful: bool,
+    /// Timestamp when the user last voted on the review.
+    pub voted_at: DateTime,
+}
+
+/// Id of a vote, unique per review and user.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReviewVoteId {
+    /// UUID of the review voted on.
+    pub review_id: Uuid,
+    /// UUID of the voting user.
+    pub user_id: Uuid,
+}
+
+/// Creates the indexes of the voting user and of the voted review.
+///
+/// The `_id` index only covers lookups by review and user.
+/// The user index is used to retrieve and remove all votes of a user,
+/// the review index to count and remove the votes on reviews.
+///
+/// * `vote_collection` - MongoDB collection of review votes.
+pub async fn create_review_vote_index(
+    vote_collection: &Collection<ReviewVote>,
+) -> mongodb::error::Result<()> {
+    let indexes = vec![
+        IndexModel::builder().keys(doc! {"_id.user_id": 1}).build(),
+        IndexModel::builder()
+            .keys(doc! {"_id.review_id": 1, "is_helpful": 1})
+            .build(),
+    ];
+    vote_collection
+        .create_indexes(indexes, None)
+        .await
+        .map(|_| ())
+}
+
+/// Recounts the helpful and unhelpful votes on a review and stores them in the review.
+///
+/// * `vote_collection` - MongoDB collection of review votes.
+/// * `review_collection` - MongoDB collection of reviews.
+/// * `review_id` - UUID of the review.
+async fn update_vote_counts(
+    vote_collection: &Collection<ReviewVote>,
+    review_collection: &Collection<Review>,
+    review_id: Uuid,
+) -> mongodb::error::Result<()> {
+    let helpful_vote_count = vote_collection
+        .count_documents(doc! {"_id.review_id": review_id, "is_helpful": true}, None)
+        .await?;
+    let unhelpful_vote_count = vote_collection
+        .count_documents(doc! {"_id.review_id": review_id, "is_helpful": false}, None)
+        .await?;
+    review_collection
+        .update_one(
+            doc! {"_id": review_id},
+            doc! {"$set": {
+                "helpful_vote_count": helpful_vote_count as i64,
+                "unhelpful_vote_count": unhelpful_vote_count as i64,
+            }},
+            None,
+        )
+        .await?;
+    Ok(())
+}
+
+/// Records the vote of a user on a review and updates the vote counts of the review.
+///
+/// An earlier vote of the user on the review is replaced.
+///
+/// * `vote_collection` - MongoDB collection of review votes.
+/// * `review_collection` - MongoDB collection of reviews.
+/// * `review_id` - UUID of the review voted on.
+/// * `user_id` - UUID of the voting user.
+/// * `is_helpful` - Flag if the user votes the review helpful, otherwise unhelpful.
+pub async fn cast_vote(
+    vote_collection: &Collection<ReviewVote>,
+    review_collection: &Collection<Review>,
+    review_id: Uuid,
+    user_id: Uuid,
+    is_helpful: bool,
+) -> mongodb::error::Result<()> {
+    let options = UpdateOptions::builder().upsert(true).build();
+    vote_collection
+        .update_one(
+            doc! {"_id": {"review_id": review_id, "user_id": user_id}},
+            doc! {"$set": {"is_helpful": is_helpful, "voted_at": DateTime::now()}},
+            options,
+        )
+        .await?;
+    update_vote_counts(vote_collection, review_collection, review_id).await
+}
+
+/// Removes the vote of a user on a review and updates the vote counts of the review.
+///
+/// Returns false if the user has not voted on the review.
+///
+/// * `vote_collection` - MongoDB collection of review votes.
+/// * `review_collection` - MongoDB collection of reviews.
+/// * `review_id` - UUID of the review voted on.
+/// * `user_id` - UUID of the voting user.
+pub async fn retract_vote(
+    vote_collection: &Collection<ReviewVote>,
+    review_collection: &Collection<Review>,
+    review_id: Uuid,
+    user_id: Uuid,
+) -> mongodb::error::Result<bool> {
+    let delete_result = vote_collection
+        .delete_one(
+            doc! {"_id": {"review_id": review_id, "user_id": user_id}},
+            None,
+        )
+        .await?;
+    if delete_result.deleted_count == 0 {
+        return Ok(false);
+    }
+    update_vote_counts(vote_collection, review_collection, review_id).await?;
+    Ok(true)
+}
+
+/// Retrieves all votes cast by a user, e.g. for a data export.
+///
+/// * `vote_collection` - MongoDB collection of review votes.
+/// * `user_id` - UUID of the voting user.
+pub async fn find_votes_of_user(
+    vote_collection: &Collection<ReviewVote>,
+    user_id: Uuid,
+) -> mongodb::error::Result<Vec<ReviewVote>> {
+    vote_collection
+        .find(doc! {"_id.user_id": user_id}, None)
+        .await?
+        .try_collect()
+        .await
+}
+
+/// Removes all votes cast by a user and updates the vote counts of the reviews voted on.
+///
+/// * `vote_collection` - MongoDB collection of review votes.
+/// * `review_collection` - MongoDB collection of reviews.
+/// * `user_id` - UUID of the voting user.
+pub async fn retract_votes_of_user(
+    vote_collection: &Collection<ReviewVote>,
+    review_collection: &Collection<Review>,
+    user_id: Uuid,
+) -> mongodb::error::Result<()> {
+    for vote in find_votes_of_user(vote_collection, user_id).await? {
+        retract_vote(
+            vote_collection,
+            review_collection,
+            vote._id.review_id,
+            user_id,
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Removes all votes on reviews, e.g. after the reviews were deleted.
+///
+/// * `vote_collection` - MongoDB collection of review votes.
+/// * `review_ids` - UUIDs of the reviews.
+pub async fn delete_votes_on_reviews(
+    vote_collection: &Collection<ReviewVote>,
+    review_ids: Vec<Uuid>,
+) -> mongodb::error::Result<()> {
+    vote_collection
+        .delete_many(doc! {"_id.review_id": {"$in": review_ids}}, None)
+        .await
+        .map(|_| ())
+}
@@ -0,0 +1,255 @@
+use std::{env, fs};
+
+use async_graphql::{Enum, SimpleObject};
+use bson::DateTime;
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+
+/// Thresholds of the reviewer ranks, read from the JSON file at `$REVIEWER_RANKS_PATH` or defaulting to
+/// `ReviewerRankThresholds::default`.
+pub static REVIEWER_RANK_THRESHOLDS: Lazy<ReviewerRankThresholds> =
+    Lazy::new(|| load_reviewer_rank_thresholds().unwrap_or_else(|message| panic!("{}", message)));
+
+/// Statistics of the reviews of a user, e.g. for reviewer cards.
+#[derive(Debug, Clone, Default, SimpleObject)]
+pub struct ReviewerStatistics {
+    /// Number of reviews.
+    pub review_count: u64,
+    /// Average rating given in reviews, `null` if the user has no reviews.
+    pub average_given_rating: Option<f32>,
+    /// Number of helpful votes received on reviews.
+    pub helpful_vote_count: u64,
+    /// Timestamp when the first review was created.
+    pub first_reviewed_at: Option<DateTime>,
+    /// Timestamp when the last review was created.
+    pub last_reviewed_at: Option<DateTime>,
+    /// Rank of the reviewer derived from the number of reviews and helpful votes.
+    pub rank: ReviewerRank,
+}
+
+/// Rank of a reviewer, e.g. shown as badge.
+#[derive(Enum, Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum ReviewerRank {
+    /// Reviewer below all thresholds.
+    #[default]
+    NewReviewer,
+    /// Reviewer reaching the `contributor` threshold.
+    Contributor,
+    /// Reviewer reaching the `trustedReviewer` threshold.
+    TrustedReviewer,
+    /// Reviewer reaching the `topReviewer` threshold.
+    TopReviewer,
+}
+
+/// Minimum numbers of reviews and helpful votes of a reviewer rank.
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub struct ReviewerRankThreshold {
+    /// Minimum number of reviews.
+    #[serde(default)]
+    pub min_review_count: u64,
+    /// Minimum number of helpful votes received on reviews.
+    #[serde(default)]
+    pub min_helpful_vote_count: u64,
+}
+
+impl ReviewerRankThreshold {
+    /// Checks if a reviewer reaches the threshold.
+    ///
+    /// * `review_count` - Number of reviews of the reviewer.
+    /// * `helpful_vote_count` - Number of helpful votes received by the reviewer.
+    fn is_reached_by(&self, review_count: u64, helpful_vote_count: u64) -> bool {
+        review_count >= self.min_review_count && helpful_vote_count >= self.min_helpful_vote_count
+    }
+
+    /// Checks if the threshold is at most as strict as another threshold in both minimums.
+    ///
+    /// * `other` - Threshold to compare with.
+    fn is_at_most(&self, other: &ReviewerRankThreshold) -> bool {
+        self.min_review_count <= other.min_review_count
+            && self.min_helpful_vote_count <= other.min_helpful_vote_count
+    }
+}
+
+/// Thresholds of the reviewer ranks above `NEW_REVIEWER`.
+///
+/// Configured by a JSON file, e.g. `{"topReviewer": {"minReviewCount": 50, "minHelpfulVoteCount": 200}}`,
+/// where missing ranks keep their default thresholds.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ReviewerRankThresholds {
+    /// Threshold of the `CONTRIBUTOR` rank.
+    pub contributor: ReviewerRankThreshold,
+    /// Threshold of the `TRUSTED_REVIEWER` rank.
+    pub trusted_reviewer: ReviewerRankThreshold,
+    /// Threshold of the `TOP_REVIEWER` rank.
+    pub top_reviewer: ReviewerRankThreshold,
+}
+
+/// Reviewers with 3 reviews are contributors, with 10 reviews and 10 helpful votes trusted reviewers
+/// and with 25 reviews and 100 helpful votes top reviewers.
+impl Default for ReviewerRankThresholds {
+    fn default() -> Self {
+        Self {
+            contributor: ReviewerRankThreshold {
+                min_review_count: 3,
+                min_helpful_vote_count: 0,
+            },
+            trusted_reviewer: ReviewerRankThreshold {
+                min_review_count: 10,
+                min_helpful_vote_count: 10,
+            },
+            top_reviewer: ReviewerRankThreshold {
+                min_review_count: 25,
+                min_helpful_vote_count: 100,
+            },
+        }
+    }
+}
+
+impl ReviewerRankThresholds {
+    /// Checks that the thresholds do not decrease from lower to higher ranks in either minimum,
+    /// so that every reviewer of a rank also reaches all lower ranks.
+    pub fn validate(&self) -> Result<(), String> {
+        let ranks = [
+            ("contributor", &self.contributor),
+            ("trustedReviewer", &self.trusted_reviewer),
+            ("topReviewer", &self.top_reviewer),
+        ];
+        for window in ranks.windows(2) {
+            let (lower_name, lower_threshold) = window[0];
+            let (higher_name, higher_threshold) = window[1];
+            if !lower_threshold.is_at_most(higher_threshold) {
+                return Err(format!(
+                    "Reviewer ranks: threshold of `{}` must not be lower than threshold of `{}`.",
+                    higher_name, lower_name
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the highest rank whose threshold a reviewer reaches.
+    ///
+    /// * `review_count` - Number of reviews of the reviewer.
+    /// * `helpful_vote_count` - Number of helpful votes received by the reviewer.
+    pub fn rank_of(&self, review_count: u64, helpful_vote_count: u64) -> ReviewerRank {
+        if self
+            .top_reviewer
+            .is_reached_by(review_count, helpful_vote_count)
+        {
+            ReviewerRank::TopReviewer
+        } else if self
+            .trusted_reviewer
+            .is_reached_by(review_count, helpful_vote_count)
+        {
+            ReviewerRank::TrustedReviewer
+        } else if self
+            .contributor
+            .is_reached_by(review_count, helpful_vote_count)
+        {
+            ReviewerRank::Contributor
+        } else {
+            ReviewerRank::NewReviewer
+        }
+    }
+}
+
+/// Loads the thresholds of the reviewer ranks.
+///
+/// The configuration file contains thresholds by rank,
+/// e.g. `{"topReviewer": {"minReviewCount": 50, "minHelpfulVoteCount": 200}}`.
+/// Fails if the thresholds decrease from lower to higher ranks.
+fn load_reviewer_rank_thresholds() -> Result<ReviewerRankThresholds, String> {
+    let thresholds = match env::var_os("REVIEWER_RANKS_PATH") {
+        Some(path) => {
+            let content = fs::read_to_string(&path).map_err(|error| {
+                format!(
+                    "Reviewer ranks: `{}` could not be read: {}",
+                    path.to_string_lossy(),
+                    error
+                )
+            })?;
+            serde_json::from_str(&content).map_err(|error| {
+                format!(
+                    "Reviewer ranks: `{}` could not be parsed: {}",
+                    path.to_string_lossy(),
+                    error
+                )
+            })?
+        }
+        None => ReviewerRankThresholds::default(),
+    };
+    thresholds.validate()?;
+    Ok(thresholds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn threshold(min_review_count: u64, min_helpful_vote_count: u64) -> ReviewerRankThreshold {
+        ReviewerRankThreshold {
+            min_review_count,
+            min_helpful_vote_count,
+        }
+    }
+
+    #[test]
+    fn ranks_reviewers_by_highest_reached_threshold() {
+        let thresholds = ReviewerRankThresholds::default();
+        assert_eq!(thresholds.rank_of(0, 0), ReviewerRank::NewReviewer);
+        assert_eq!(thresholds.rank_of(2, 500), ReviewerRank::NewReviewer);
+        assert_eq!(thresholds.rank_of(3, 0), ReviewerRank::Contributor);
+        assert_eq!(thresholds.rank_of(10, 9), ReviewerRank::Contributor);
+        assert_eq!(thresholds.rank_of(10, 10), ReviewerRank::TrustedReviewer);
+        assert_eq!(thresholds.rank_of(100, 99), ReviewerRank::TrustedReviewer);
+        assert_eq!(thresholds.rank_of(25, 100), ReviewerRank::TopReviewer);
+    }
+
+    #[test]
+    fn keeps_default_thresholds_of_missing_ranks() {
+        let thresholds: ReviewerRankThresholds = serde_json::from_str(
+            r#"{"topReviewer": {"minReviewCount": 50, "minHelpfulVoteCount": 200}}"#,
+        )
+        .unwrap();
+        assert_eq!(thresholds.rank_of(25, 100), ReviewerRank::TrustedReviewer);
+        assert_eq!(thresholds.rank_of(50, 200), ReviewerRank::TopReviewer);
+        assert_eq!(thresholds.validate(), Ok(()));
+    }
+
+    #[test]
+    fn accepts_default_and_equal_thresholds() {
+        assert_eq!(ReviewerRankThresholds::default().validate(), Ok(()));
+        let thresholds = ReviewerRankThresholds {
+            contributor: threshold(5, 5),
+            trusted_reviewer: threshold(5, 5),
+            top_reviewer: threshold(5, 5),
+        };
+        assert_eq!(thresholds.validate(), Ok(()));
+    }
+
+    #[test]
+    fn rejects_decreasing_review_counts() {
+        let thresholds = ReviewerRankThresholds {
+            contributor: threshold(3, 0),
+            trusted_reviewer: threshold(30, 10),
+            top_reviewer: threshold(25, 100),
+        };
+        let error = thresholds.validate().unwrap_err();
+        assert!(error.contains("`topReviewer`"));
+        assert!(error.contains("`trustedReviewer`"));
+    }
+
+    #[test]
+    fn rejects_decreasing_helpful_vote_counts() {
+        let thresholds = ReviewerRankThresholds {
+            contributor: threshold(3, 20),
+            trusted_reviewer: threshold(10, 10),
+            top_reviewer: threshold(25, 100),
+        };
+        let error = thresholds.validate().unwrap_err();
+        assert!(error.contains("`trustedReviewer`"));
+        assert!(error.contains("`contributor`"));
+    }
+}
@@ -1,16 +1,20 @@
-use async_graphql::{ComplexObject, Context, Error, Result, SimpleObject};
-use bson::{doc, Document, Uuid};
-use mongodb::{options::FindOptions, Collection, Database};
-use mongodb_cursor_pagination::{error::CursorError, FindResult, PaginatedCursor};
+use async_graphql::{dataloader::DataLoader, ComplexObject, Context, Error, Result, SimpleObject};
+use bson::Uuid;
 use serde::{Deserialize, Serialize};
 
-use super::{
-    connection::{
-        base_connection::{BaseConnection, FindResultWrapper},
-        review_connection::ReviewConnection,
+use crate::authorization::{is_authorized, permission::Permission};
+use crate::graphql::{
+    loader::{
+        review_connection_loader::{ReviewConnectionKey, ReviewConnectionLoader, ReviewPage},
+        reviewer_statistics_loader::ReviewerStatisticsLoader,
+        ReviewParent,
     },
-    order_datatypes::ReviewOrderInput,
-    review::Review,
+    query_limits::{connection_complexity, RATING_SUMMARY_COMPLEXITY},
+};
+
+use super::{
+    connection::review_connection::ReviewConnection, order_datatypes::ReviewOrderInput,
+    reviewer_statistics::ReviewerStatistics,
 };
 
 /// Type of a user owning reviews.
@@ -24,40 +28,47 @@ pub struct User {
 #[ComplexObject]
 impl User {
     /// Retrieves reviews of user.
+    ///
+    /// Anonymous and hidden reviews are only retrieved for the user itself or users with the `review:moderate` permission.
+    #[graphql(complexity = "connection_complexity(first, child_complexity)")]
     async fn reviews<'a>(
         &self,
         ctx: &Context<'a>,
-        #[graphql(desc = "Describes that the `first` N reviews should be retrieved.")]
+        #[graphql(
+            desc = "Describes that the `first` N reviews should be retrieved, by default 20 and at most 100."
+        )]
         first: Option<u32>,
         #[graphql(desc = "Describes how many reviews should be skipped at the beginning.")]
         skip: Option<u64>,
-        #[graphql(desc = "Specifies the order in which reviews are retrieved.")] order_by: Option<
-            ReviewOrderInput,
-        >,
+        #[graphql(
+            desc = "Specifies the order in which reviews are retrieved, later order inputs break ties of earlier ones."
+        )]
+        order_by: Option<Vec<ReviewOrderInput>>,
+        #[graphql(desc = "Only retrieves reviews of product variants in this product category.")]
+        product_category: Option<String>,
     ) -> Result<ReviewConnection> {
-        let db_client = ctx.data::<Database>()?;
-        let collection: Collection<Review> = db_client.collection::<Review>("reviews");
-        let review_order = order_by.unwrap_or_default();
-        let sorting_doc = doc! {review_order.field.unwrap_or_default().as_str(): i32::from(review_order.direction.unwrap_or_default())};
-        let find_options = FindOptions::builder()
-            .skip(skip)
-            .limit(first.map(|definitely_first| i64::from(definitely_first)))
-            .sort(sorting_doc)
-            .build();
-        let document_collection = collection.clone_with_type::<Document>();
-        let filter = doc! {"user._id": self._id};
-        let maybe_find_results: Result<FindResult<Review>, CursorError> =
-            PaginatedCursor::new(Some(find_options.clone()), None, None)
-                .find(&document_collection, Some(&filter))
-                .await;
-        match maybe_find_results {
-            Ok(find_results) => {
-                let find_result_wrapper = FindResultWrapper(find_results);
-                let connection = Into::<BaseConnection<Review>>::into(find_result_wrapper);
-                Ok(Into::<ReviewConnection>::into(connection))
-            }
-            Err(_) => return Err(Error::new("Retrieving reviews failed in MongoDB.")),
-        }
+        let mut page = ReviewPage::new(ctx, first, skip, order_by)?;
+        page.include_anonymous = is_authorized(ctx, Some(self._id), Permission::ModerateReviews);
+        page.product_category = product_category;
+        let key = ReviewConnectionKey {
+            parent: ReviewParent::User(self._id),
+            page,
+        };
+        let review_connection_loader = ctx.data::<DataLoader<ReviewConnectionLoader>>()?;
+        review_connection_loader
+            .load_one(key)
+            .await?
+            .ok_or_else(|| Error::new("Retrieving reviews failed in MongoDB."))
+    }
+
+    /// Retrieves statistics and rank of user as reviewer, e.g. for reviewer cards.
+    ///
+    /// Only visible reviews which are not anonymous are included.
+    #[graphql(complexity = "RATING_SUMMARY_COMPLEXITY")]
+    async fn reviewer_statistics<'a>(&self, ctx: &Context<'a>) -> Result<ReviewerStatistics> {
+        let reviewer_statistics_loader = ctx.data::<DataLoader<ReviewerStatisticsLoader>>()?;
+        let maybe_reviewer_statistics = reviewer_statistics_loader.load_one(self._id).await?;
+        Ok(maybe_reviewer_statistics.unwrap_or_default())
     }
 }
 
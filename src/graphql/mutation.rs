@@ -1,19 +1,34 @@
 use async_graphql::{Context, Error, Object, Result};
 use bson::Bson;
 use bson::Uuid;
+use futures::TryStreamExt;
 use mongodb::{
     bson::{doc, DateTime},
+    options::FindOptions,
     Collection, Database,
 };
+use opentelemetry::KeyValue;
 
-use crate::authorization::authorize_user;
+use crate::authorization::{
+    authorize_user, authorized_user, check_permissions, permission::Permission,
+    AuthorizedUserHeader,
+};
+use crate::event::{
+    dead_letter::replay_failed_event,
+    http_event_service::{erase_user_data, HttpEventServiceState},
+};
+use crate::telemetry::metrics::{rating_attribute, METRICS};
 
+use super::model::failed_event::{FailedEventReplay, FailedEventReplayFailure};
 use super::model::product_variant::ProductVariant;
-use super::model::review::Review;
+use super::model::review::{validate_media_urls, Review};
+use super::model::review_vote::{cast_vote, delete_votes_on_reviews, retract_vote, ReviewVote};
 use super::model::user::User;
 use super::mutation_input_structs::CreateReviewInput;
 use super::mutation_input_structs::UpdateReviewInput;
 use super::query::query_object;
+use super::query_limits::page_size;
+use super::response_cache::RESPONSE_CACHE;
 
 /// Describes GraphQL review mutations.
 pub struct Mutation;
@@ -26,7 +41,10 @@ impl Mutation {
         ctx: &Context<'a>,
         #[graphql(desc = "CreateReviewInput")] input: CreateReviewInput,
     ) -> Result<Review> {
-        authorize_user(&ctx, Some(input.user_id))?;
+        authorize_user(ctx, Some(input.user_id), Permission::WriteAnyReview)?;
+        if input.is_verified_purchase.is_some() {
+            authorize_user(ctx, None, Permission::WriteAnyReview)?;
+        }
         let db_client = ctx.data::<Database>()?;
         let product_variant_collection: Collection<ProductVariant> =
             db_client.collection::<ProductVariant>("product_variants");
@@ -44,9 +62,19 @@ impl Mutation {
             created_at: current_timestamp,
             last_updated_at: current_timestamp,
             is_visible: input.is_visible.unwrap_or(true),
+            is_anonymous: input.is_anonymous.unwrap_or(false),
+            helpful_vote_count: 0,
+            unhelpful_vote_count: 0,
+            is_verified_purchase: input.is_verified_purchase.unwrap_or(false),
+            media_urls: input.media_urls.clone().unwrap_or_default(),
         };
         review_is_already_written_by_user(&review_collection, &input).await?;
-        insert_review_in_mongodb(&review_collection, review).await
+        let review = insert_review_in_mongodb(&review_collection, review).await?;
+        RESPONSE_CACHE.invalidate_review(&review).await;
+        METRICS
+            .reviews_created
+            .add(1, &[rating_attribute(review.rating)]);
+        Ok(review)
     }
 
     /// Updates a specific review referenced with an UUID.
@@ -59,11 +87,22 @@ impl Mutation {
         let collection: Collection<Review> = db_client.collection::<Review>("reviews");
         let current_timestamp = DateTime::now();
         let review = query_object(&collection, input.id).await?;
-        authorize_user(&ctx, Some(review.user._id))?;
+        authorize_review_update(ctx, &review, &input)?;
+        if let Some(definitely_media_urls) = &input.media_urls {
+            validate_media_urls(definitely_media_urls).map_err(Error::new)?;
+        }
         update_body(&collection, &input, &current_timestamp).await?;
         update_rating(&collection, &input, &current_timestamp).await?;
         update_visibility(&collection, &input, &current_timestamp).await?;
+        update_anonymity(&collection, &input, &current_timestamp).await?;
+        update_media_urls(&collection, &input, &current_timestamp).await?;
+        update_verified_purchase(&collection, &input, &current_timestamp).await?;
+        record_moderation_action(ctx, &review, &input);
         let review = query_object(&collection, input.id).await?;
+        RESPONSE_CACHE.invalidate_review(&review).await;
+        METRICS
+            .reviews_updated
+            .add(1, &[rating_attribute(review.rating)]);
         Ok(review)
     }
 
@@ -76,13 +115,298 @@ impl Mutation {
         let db_client = ctx.data::<Database>()?;
         let collection: Collection<Review> = db_client.collection::<Review>("reviews");
         let review = query_object(&collection, id).await?;
-        authorize_user(&ctx, Some(review.user._id))?;
-        if let Err(_) = collection.delete_one(doc! {"_id": id }, None).await {
+        authorize_user(ctx, Some(review.user._id), Permission::DeleteAnyReview)?;
+        if collection
+            .delete_one(doc! {"_id": id }, None)
+            .await
+            .is_err()
+        {
             let message = format!("Deleting review of id: `{}` failed in MongoDB.", id);
             return Err(Error::new(message));
         }
+        let vote_collection: Collection<ReviewVote> =
+            db_client.collection::<ReviewVote>("review_votes");
+        if delete_votes_on_reviews(&vote_collection, vec![id])
+            .await
+            .is_err()
+        {
+            let message = format!(
+                "Deleting votes on review of id: `{}` failed in MongoDB.",
+                id
+            );
+            return Err(Error::new(message));
+        }
+        RESPONSE_CACHE.invalidate_review(&review).await;
+        METRICS
+            .reviews_deleted
+            .add(1, &[rating_attribute(review.rating)]);
         Ok(true)
     }
+
+    /// Votes whether a review of another user is helpful, replacing an earlier vote of the authorized user.
+    async fn vote_review<'a>(
+        &self,
+        ctx: &Context<'a>,
+        #[graphql(desc = "UUID of review to vote on.")] id: Uuid,
+        #[graphql(desc = "Flag if the review is helpful, otherwise it is unhelpful.")]
+        is_helpful: bool,
+    ) -> Result<Review> {
+        let authorized_user_header = authorized_user(ctx)?;
+        let db_client = ctx.data::<Database>()?;
+        let review_collection: Collection<Review> = db_client.collection::<Review>("reviews");
+        let vote_collection: Collection<ReviewVote> =
+            db_client.collection::<ReviewVote>("review_votes");
+        let review = query_visible_review(ctx, &review_collection, id).await?;
+        if review.user._id == authorized_user_header.id {
+            return Err(Error::new("Users can not vote on their own reviews."));
+        }
+        if cast_vote(
+            &vote_collection,
+            &review_collection,
+            id,
+            authorized_user_header.id,
+            is_helpful,
+        )
+        .await
+        .is_err()
+        {
+            let message = format!("Voting on review of id: `{}` failed in MongoDB.", id);
+            return Err(Error::new(message));
+        }
+        RESPONSE_CACHE.invalidate_review(&review).await;
+        query_object(&review_collection, id).await
+    }
+
+    /// Removes the vote of the authorized user on a review.
+    async fn retract_review_vote<'a>(
+        &self,
+        ctx: &Context<'a>,
+        #[graphql(desc = "UUID of review to remove the vote from.")] id: Uuid,
+    ) -> Result<Review> {
+        let authorized_user_header = authorized_user(ctx)?;
+        let db_client = ctx.data::<Database>()?;
+        let review_collection: Collection<Review> = db_client.collection::<Review>("reviews");
+        let vote_collection: Collection<ReviewVote> =
+            db_client.collection::<ReviewVote>("review_votes");
+        let review = query_visible_review(ctx, &review_collection, id).await?;
+        match retract_vote(
+            &vote_collection,
+            &review_collection,
+            id,
+            authorized_user_header.id,
+        )
+        .await
+        {
+            Ok(true) => {}
+            Ok(false) => {
+                let message = format!("User has not voted on review of id: `{}`.", id);
+                return Err(Error::new(message));
+            }
+            Err(_) => {
+                let message = format!("Removing vote on review of id: `{}` failed in MongoDB.", id);
+                return Err(Error::new(message));
+            }
+        }
+        RESPONSE_CACHE.invalidate_review(&review).await;
+        query_object(&review_collection, id).await
+    }
+
+    /// Erases all data tied to a user, e.g. to fulfill a data-subject request.
+    ///
+    /// Requires the `user-data:erase` permission. Returns the number of deleted reviews.
+    async fn erase_user_data<'a>(
+        &self,
+        ctx: &Context<'a>,
+        #[graphql(desc = "UUID of user to erase the data of.")] user_id: Uuid,
+    ) -> Result<u64> {
+        authorize_user(ctx, None, Permission::EraseUserData)?;
+        let db_client = ctx.data::<Database>()?;
+        let state = HttpEventServiceState::new(db_client);
+        match erase_user_data(&state, user_id).await {
+            Ok(deleted_review_count) => Ok(deleted_review_count),
+            Err(error) => {
+                let message = format!(
+                    "Erasing data of user of UUID: `{}` failed: {}",
+                    user_id, error
+                );
+                Err(Error::new(message))
+            }
+        }
+    }
+
+    /// Replays a failed event of an id through the normal event handler.
+    ///
+    /// Requires the `event:manage` permission. Removes the failed event if the replay succeeds.
+    async fn replay_failed_event<'a>(
+        &self,
+        ctx: &Context<'a>,
+        #[graphql(desc = "Id of the CloudEvent to replay.")] id: String,
+    ) -> Result<bool> {
+        authorize_user(ctx, None, Permission::ManageEvents)?;
+        let db_client = ctx.data::<Database>()?;
+        let state = HttpEventServiceState::new(db_client);
+        let failed_event = match state
+            .failed_event_collection
+            .find_one(doc! {"_id": &id }, None)
+            .await
+        {
+            Ok(Some(failed_event)) => failed_event,
+            _ => {
+                let message = format!("Failed event with id: `{}` not found.", id);
+                return Err(Error::new(message));
+            }
+        };
+        match replay_failed_event(&state, failed_event).await {
+            Ok(()) => Ok(true),
+            Err(error) => {
+                let message = format!("Replaying event of id: `{}` failed: {}", id, error);
+                Err(Error::new(message))
+            }
+        }
+    }
+
+    /// Replays the oldest replayable failed events through the normal event handler.
+    ///
+    /// Requires the `event:manage` permission. Returns the number of successfully replayed events and the failures.
+    async fn replay_failed_events<'a>(
+        &self,
+        ctx: &Context<'a>,
+        #[graphql(
+            desc = "Describes that the `first` N failed events should be replayed, by default 20 and at most 100."
+        )]
+        first: Option<u32>,
+        #[graphql(desc = "Only replays failed events of this topic.")] topic: Option<String>,
+    ) -> Result<FailedEventReplay> {
+        authorize_user(ctx, None, Permission::ManageEvents)?;
+        let db_client = ctx.data::<Database>()?;
+        let state = HttpEventServiceState::new(db_client);
+        let mut filter = doc! {"replayable": true};
+        if let Some(definitely_topic) = topic {
+            filter.insert("topic", definitely_topic);
+        }
+        let find_options = FindOptions::builder()
+            .limit(page_size(first)?)
+            .sort(doc! {"first_failed_at": 1})
+            .build();
+        let mut cursor = match state
+            .failed_event_collection
+            .find(filter, find_options)
+            .await
+        {
+            Ok(cursor) => cursor,
+            Err(_) => return Err(Error::new("Retrieving failed events failed in MongoDB.")),
+        };
+        let mut failed_event_replay = FailedEventReplay::default();
+        while let Some(failed_event) = cursor.try_next().await? {
+            let id = failed_event._id.clone();
+            match replay_failed_event(&state, failed_event).await {
+                Ok(()) => failed_event_replay.replayed_count += 1,
+                Err(error) => failed_event_replay.failures.push(FailedEventReplayFailure {
+                    id,
+                    error: error.to_string(),
+                }),
+            }
+        }
+        Ok(failed_event_replay)
+    }
+}
+
+/// Retrieves a review of UUID if it is visible to the user of a context.
+///
+/// * `ctx` - GraphQL context which may contain the `Authorized-User` header.
+/// * `collection` - MongoDB collection of reviews.
+/// * `id` - UUID of the review.
+async fn query_visible_review(
+    ctx: &Context<'_>,
+    collection: &Collection<Review>,
+    id: Uuid,
+) -> Result<Review> {
+    let review = query_object(collection, id).await?;
+    if review.is_visible_to(ctx) {
+        Ok(review)
+    } else {
+        let message = format!("Review with UUID: `{}` not found.", id);
+        Err(Error::new(message))
+    }
+}
+
+/// Authorizes an update of a review for the user of a context.
+///
+/// * `ctx` - GraphQL context containing the `Authorized-User` header.
+/// * `review` - Review before the update.
+/// * `input` - Update review input containing the changed fields.
+fn authorize_review_update(
+    ctx: &Context,
+    review: &Review,
+    input: &UpdateReviewInput,
+) -> Result<()> {
+    let authorized_user_header = authorized_user(ctx)?;
+    check_review_update_permissions(authorized_user_header, review.user._id, input)
+}
+
+/// Checks if a user may update a review.
+///
+/// The author can update the body, rating, anonymity and media URLs, other users need the `review:write:any`
+/// permission. Changing the visibility always requires the `review:moderate` permission, also for the author,
+/// so that authors cannot show reviews hidden by a moderator. Changing the verified purchase flag always requires
+/// the `review:write:any` permission.
+///
+/// * `authorized_user_header` - `Authorized-User` header of the user updating the review.
+/// * `author_id` - UUID of the author of the review.
+/// * `input` - Update review input containing the changed fields.
+fn check_review_update_permissions(
+    authorized_user_header: &AuthorizedUserHeader,
+    author_id: Uuid,
+    input: &UpdateReviewInput,
+) -> Result<()> {
+    if input.is_visible.is_some() {
+        check_permissions(authorized_user_header, None, Permission::ModerateReviews)?;
+    }
+    if input.is_verified_purchase.is_some() {
+        check_permissions(authorized_user_header, None, Permission::WriteAnyReview)?;
+    }
+    let changes_content = input.body.is_some()
+        || input.rating.is_some()
+        || input.is_anonymous.is_some()
+        || input.media_urls.is_some();
+    if changes_content || input.is_visible.is_none() {
+        check_permissions(
+            authorized_user_header,
+            Some(author_id),
+            Permission::WriteAnyReview,
+        )?;
+    }
+    Ok(())
+}
+
+/// Records a change of the visibility of a review as moderation action.
+///
+/// A visibility change is performed by a moderator if the authorized user is not the author of the review.
+///
+/// * `ctx` - GraphQL context containing the `Authorized-User` header.
+/// * `review` - Review before the update.
+/// * `input` - Update review input containing the new visibility.
+fn record_moderation_action(ctx: &Context, review: &Review, input: &UpdateReviewInput) {
+    if let Some(definitely_is_visible) = input.is_visible
+        && definitely_is_visible != review.is_visible
+    {
+        let action = if definitely_is_visible {
+            "show"
+        } else {
+            "hide"
+        };
+        let by_moderator = ctx
+            .data::<AuthorizedUserHeader>()
+            .map(|authorized_user_header| authorized_user_header.id != review.user._id)
+            .unwrap_or(false);
+        METRICS.moderation_actions.add(
+            1,
+            &[
+                KeyValue::new("action", action),
+                KeyValue::new("by_moderator", by_moderator),
+            ],
+        );
+    }
 }
 
 /// Extracts UUID from BSON.
@@ -112,7 +436,7 @@ async fn insert_review_in_mongodb(
     match collection.insert_one(review, None).await {
         Ok(result) => {
             let id = uuid_from_bson(result.inserted_id)?;
-            query_object(&collection, id).await
+            query_object(collection, id).await
         }
         Err(_) => Err(Error::new("Adding review failed in MongoDB.")),
     }
@@ -128,21 +452,21 @@ async fn update_body(
     input: &UpdateReviewInput,
     current_timestamp: &DateTime,
 ) -> Result<()> {
-    if let Some(definitely_body) = &input.body {
-        if let Err(_) = collection
+    if let Some(definitely_body) = &input.body
+        && collection
             .update_one(
                 doc! {"_id": input.id },
                 doc! {"$set": {"body": definitely_body, "last_updated_at": current_timestamp}},
                 None,
             )
             .await
-        {
-            let message = format!(
-                "Updating body of review of id: `{}` failed in MongoDB.",
-                input.id
-            );
-            return Err(Error::new(message));
-        }
+            .is_err()
+    {
+        let message = format!(
+            "Updating body of review of id: `{}` failed in MongoDB.",
+            input.id
+        );
+        return Err(Error::new(message));
     }
     Ok(())
 }
@@ -157,21 +481,21 @@ async fn update_rating(
     input: &UpdateReviewInput,
     current_timestamp: &DateTime,
 ) -> Result<()> {
-    if let Some(definitely_rating) = &input.rating {
-        if let Err(_) = collection
+    if let Some(definitely_rating) = &input.rating
+        && collection
             .update_one(
                 doc! {"_id": input.id },
                 doc! {"$set": {"rating": definitely_rating, "last_updated_at": current_timestamp}},
                 None,
             )
             .await
-        {
-            let message = format!(
-                "Updating rating of review of id: `{}` failed in MongoDB.",
-                input.id
-            );
-            return Err(Error::new(message));
-        }
+            .is_err()
+    {
+        let message = format!(
+            "Updating rating of review of id: `{}` failed in MongoDB.",
+            input.id
+        );
+        return Err(Error::new(message));
     }
     Ok(())
 }
@@ -186,16 +510,114 @@ async fn update_visibility(
     input: &UpdateReviewInput,
     current_timestamp: &DateTime,
 ) -> Result<()> {
-    if let Some(definitely_is_visible) = &input.is_visible {
-        if let Err(_) = collection.update_one(doc!{"_id": input.id }, doc!{"$set": {"is_visible": definitely_is_visible, "last_updated_at": current_timestamp}}, None).await {
-            let message = format!("Updating visibility of review of id: `{}` failed in MongoDB.", input.id);
-            return Err(Error::new(message))
-        }
+    if let Some(definitely_is_visible) = &input.is_visible
+        && collection
+            .update_one(
+                doc! {"_id": input.id },
+                doc! {"$set": {"is_visible": definitely_is_visible, "last_updated_at": current_timestamp}},
+                None,
+            )
+            .await
+            .is_err()
+    {
+        let message = format!(
+            "Updating visibility of review of id: `{}` failed in MongoDB.",
+            input.id
+        );
+        return Err(Error::new(message));
+    }
+    Ok(())
+}
+
+/// Updates anonymity of a review.
+///
+/// * `collection` - MongoDB collection to update.
+/// * `input` - Update review input containing new anonymity.
+/// * `current_timestamp` - Timestamp of review anonymity update.
+async fn update_anonymity(
+    collection: &Collection<Review>,
+    input: &UpdateReviewInput,
+    current_timestamp: &DateTime,
+) -> Result<()> {
+    if let Some(definitely_is_anonymous) = &input.is_anonymous
+        && collection
+            .update_one(
+                doc! {"_id": input.id },
+                doc! {"$set": {"is_anonymous": definitely_is_anonymous, "last_updated_at": current_timestamp}},
+                None,
+            )
+            .await
+            .is_err()
+    {
+        let message = format!(
+            "Updating anonymity of review of id: `{}` failed in MongoDB.",
+            input.id
+        );
+        return Err(Error::new(message));
     }
     Ok(())
 }
 
-/// Checks if product variants and user in create review input are in the system (MongoDB database populated with events).
+/// Updates media URLs of a review.
+///
+/// * `collection` - MongoDB collection to update.
+/// * `input` - Update review input containing new media URLs.
+/// * `current_timestamp` - Timestamp of review media URLs update.
+async fn update_media_urls(
+    collection: &Collection<Review>,
+    input: &UpdateReviewInput,
+    current_timestamp: &DateTime,
+) -> Result<()> {
+    if let Some(definitely_media_urls) = &input.media_urls
+        && collection
+            .update_one(
+                doc! {"_id": input.id },
+                doc! {"$set": {"media_urls": definitely_media_urls, "last_updated_at": current_timestamp}},
+                None,
+            )
+            .await
+            .is_err()
+    {
+        let message = format!(
+            "Updating media URLs of review of id: `{}` failed in MongoDB.",
+            input.id
+        );
+        return Err(Error::new(message));
+    }
+    Ok(())
+}
+
+/// Updates verified purchase flag of a review.
+///
+/// * `collection` - MongoDB collection to update.
+/// * `input` - Update review input containing new verified purchase flag.
+/// * `current_timestamp` - Timestamp of review verified purchase flag update.
+async fn update_verified_purchase(
+    collection: &Collection<Review>,
+    input: &UpdateReviewInput,
+    current_timestamp: &DateTime,
+) -> Result<()> {
+    if let Some(definitely_is_verified_purchase) = &input.is_verified_purchase
+        && collection
+            .update_one(
+                doc! {"_id": input.id },
+                doc! {"$set": {"is_verified_purchase": definitely_is_verified_purchase, "last_updated_at": current_timestamp}},
+                None,
+            )
+            .await
+            .is_err()
+    {
+        let message = format!(
+            "Updating verified purchase flag of review of id: `{}` failed in MongoDB.",
+            input.id
+        );
+        return Err(Error::new(message));
+    }
+    Ok(())
+}
+
+/// Checks if product variants and user in create review input are in the system (MongoDB database populated with events)
+/// and if the media URLs are valid.
 ///
 /// * `db_client` - MongoDB database client.
 /// * `input` - Create review input containing information to create review.
@@ -205,10 +627,13 @@ async fn validate_input(db_client: &Database, input: &CreateReviewInput) -> Resu
     let user_collection: Collection<User> = db_client.collection::<User>("users");
     validate_product_variant_id(&product_variant_collection, input.product_variant_id).await?;
     validate_user(&user_collection, input.user_id).await?;
+    if let Some(definitely_media_urls) = &input.media_urls {
+        validate_media_urls(definitely_media_urls).map_err(Error::new)?;
+    }
     Ok(())
 }
 
-/// Checks if product variant in is in the system (MongoDB database populated with events).
+/// Checks if product variant in is in the system (MongoDB database populated with events) and active.
 ///
 /// Used before adding reviews.
 ///
@@ -227,6 +652,13 @@ async fn validate_product_variant_id(
         .await
     {
         Ok(maybe_product_variant) => match maybe_product_variant {
+            Some(product_variant) if !product_variant.is_active => {
+                let message = format!(
+                    "Product variant with the UUID: `{}` is inactive and can not be reviewed.",
+                    product_variant_id
+                );
+                Err(Error::new(message))
+            }
             Some(_) => Ok(()),
             None => Err(Error::new(message)),
         },
@@ -241,7 +673,7 @@ async fn validate_product_variant_id(
 /// * `collection` - MongoDB collection to validate against.
 /// * `id` - User UUID to validate.
 async fn validate_user(collection: &Collection<User>, id: Uuid) -> Result<()> {
-    query_object(&collection, id).await.map(|_| ())
+    query_object(collection, id).await.map(|_| ())
 }
 
 /// Throws an error if user has already written a review for the product variant.
@@ -264,9 +696,87 @@ async fn review_is_already_written_by_user(
         .await
     {
         Ok(maybe_product_variant) => match maybe_product_variant {
-            Some(_) => Err(Error::new(message)),
+            Some(_) => {
+                METRICS.duplicate_reviews_rejected.add(1, &[]);
+                Err(Error::new(message))
+            }
             None => Ok(()),
         },
         Err(_) => Err(Error::new(message)),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphql::model::review::Rating;
+
+    const AUTHOR_ID: &str = "6f4d1a3e-2b8c-4e5f-9a0b-1c2d3e4f5a6b";
+
+    fn authorized_user_header(id: &str, role: &str) -> AuthorizedUserHeader {
+        let header = format!(r#"{{"id": "{}", "roles": ["{}"]}}"#, id, role);
+        serde_json::from_str(&header).unwrap()
+    }
+
+    fn update_review_input() -> UpdateReviewInput {
+        UpdateReviewInput {
+            id: Uuid::new(),
+            body: None,
+            rating: None,
+            is_visible: None,
+            is_anonymous: None,
+            media_urls: None,
+            is_verified_purchase: None,
+        }
+    }
+
+    fn author_id() -> Uuid {
+        Uuid::parse_str(AUTHOR_ID).unwrap()
+    }
+
+    #[test]
+    fn author_cannot_show_review_hidden_by_moderator() {
+        let author = authorized_user_header(AUTHOR_ID, "buyer");
+        let input = UpdateReviewInput {
+            is_visible: Some(true),
+            ..update_review_input()
+        };
+        let error = check_review_update_permissions(&author, author_id(), &input).unwrap_err();
+        assert!(error.message.contains("`review:moderate`"));
+    }
+
+    #[test]
+    fn moderator_can_change_visibility_of_review() {
+        let moderator = authorized_user_header("0b6e9c1a-7d2f-4a3b-8c5d-9e0f1a2b3c4d", "employee");
+        let input = UpdateReviewInput {
+            is_visible: Some(true),
+            ..update_review_input()
+        };
+        assert!(check_review_update_permissions(&moderator, author_id(), &input).is_ok());
+    }
+
+    #[test]
+    fn author_can_update_content_but_not_verified_purchase() {
+        let author = authorized_user_header(AUTHOR_ID, "buyer");
+        let input = UpdateReviewInput {
+            body: Some("Still great after a month.".to_string()),
+            ..update_review_input()
+        };
+        assert!(check_review_update_permissions(&author, author_id(), &input).is_ok());
+        let input = UpdateReviewInput {
+            is_verified_purchase: Some(true),
+            ..update_review_input()
+        };
+        assert!(check_review_update_permissions(&author, author_id(), &input).is_err());
+    }
+
+    #[test]
+    fn other_buyer_cannot_update_content() {
+        let other_buyer = authorized_user_header("0b6e9c1a-7d2f-4a3b-8c5d-9e0f1a2b3c4d", "buyer");
+        let input = UpdateReviewInput {
+            rating: Some(Rating::OneStars),
+            ..update_review_input()
+        };
+        assert!(check_review_update_permissions(&other_buyer, author_id(), &input).is_err());
+    }
+}
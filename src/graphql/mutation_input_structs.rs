@@ -15,6 +15,13 @@ pub struct CreateReviewInput {
     pub rating: Rating,
     /// Flag if review is visible, by default set to true.
     pub is_visible: Option<bool>,
+    /// Flag if review is displayed without its author, by default set to false.
+    pub is_anonymous: Option<bool>,
+    /// URLs of images and videos attached to review, at most 10 absolute HTTP(S) URLs.
+    pub media_urls: Option<Vec<String>>,
+    /// Flag if the user purchased the product variant in review, by default set to false.
+    /// Requires the `review:write:any` permission, also for the author.
+    pub is_verified_purchase: Option<bool>,
 }
 
 #[derive(SimpleObject, InputObject)]
@@ -26,5 +33,13 @@ pub struct UpdateReviewInput {
     /// Rating of review in 1-5 stars to update.
     pub rating: Option<Rating>,
     /// Flag if review is visible.
+    /// Requires the `review:moderate` permission, also for the author.
     pub is_visible: Option<bool>,
+    /// Flag if review is displayed without its author.
+    pub is_anonymous: Option<bool>,
+    /// URLs of images and videos attached to review, at most 10 absolute HTTP(S) URLs, replacing all attached URLs.
+    pub media_urls: Option<Vec<String>>,
+    /// Flag if the user purchased the product variant in review.
+    /// Requires the `review:write:any` permission, also for the author.
+    pub is_verified_purchase: Option<bool>,
 }
@@ -0,0 +1,428 @@
+use std::{collections::HashMap, env, fs, num::NonZeroUsize, sync::Arc};
+
+use async_graphql::{
+    extensions::{Extension, ExtensionContext, ExtensionFactory, NextPrepareRequest},
+    from_value,
+    parser::{
+        parse_query,
+        types::{Field, OperationType, Selection, SelectionSet},
+    },
+    Error, ErrorExtensions, Request, ServerError, ServerResult,
+};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use store::{LruPersistedQueryStore, PersistedQueryStore};
+
+pub mod store;
+
+/// Number of queries held by the in-memory store if `$PERSISTED_QUERIES_CACHE_SIZE` is not set.
+const DEFAULT_CACHE_SIZE: usize = 1000;
+
+/// Name of the request extension referencing a persisted query.
+const PERSISTED_QUERY_EXTENSION: &str = "persistedQuery";
+
+/// Fields of `_service` queried by the federation gateway to compose the supergraph,
+/// which are executed in allowlist mode without being persisted.
+///
+/// `_entities` is not exempted, as it resolves arbitrary selections on entities,
+/// so entity queries of the gateway have to be part of the manifest.
+const SERVICE_FIELDS: [&str; 2] = ["sdl", "__typename"];
+
+/// Handling of operations which are not persisted.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PersistedQueryMode {
+    /// Clients register queries by sending them with their hash once, afterwards the hash suffices.
+    #[default]
+    Automatic,
+    /// Only operations of the manifest are executed, e.g. in production.
+    Allowlist,
+}
+
+/// Manifest of persisted queries in the format of the Apollo persisted query manifest.
+#[derive(Deserialize, Debug)]
+struct PersistedQueryManifest {
+    operations: Vec<PersistedOperation>,
+}
+
+/// Operation of a persisted query manifest.
+#[derive(Deserialize, Debug)]
+struct PersistedOperation {
+    /// Hex encoded SHA-256 hash of the body.
+    id: String,
+    /// GraphQL query.
+    body: String,
+}
+
+/// `persistedQuery` extension of a request.
+#[derive(Deserialize)]
+struct PersistedQuery {
+    version: i32,
+    #[serde(rename = "sha256Hash")]
+    sha256_hash: String,
+}
+
+/// GraphQL extension resolving persisted queries by their SHA-256 hash.
+///
+/// Supports automatic persisted queries, where clients register queries at runtime,
+/// and an allowlist mode, which rejects all operations missing in the manifest.
+pub struct PersistedQueries {
+    mode: PersistedQueryMode,
+    allowlist: Arc<HashMap<String, String>>,
+    store: Arc<dyn PersistedQueryStore>,
+}
+
+impl PersistedQueries {
+    /// Creates the extension.
+    ///
+    /// * `mode` - Handling of operations which are not persisted.
+    /// * `allowlist` - Queries of the manifest by their hash.
+    /// * `store` - Store of queries registered by clients, unused in allowlist mode.
+    pub fn new(
+        mode: PersistedQueryMode,
+        allowlist: HashMap<String, String>,
+        store: Arc<dyn PersistedQueryStore>,
+    ) -> Self {
+        Self {
+            mode,
+            allowlist: Arc::new(allowlist),
+            store,
+        }
+    }
+
+    /// Creates the extension from the environment variables `$PERSISTED_QUERIES_MODE`,
+    /// `$PERSISTED_QUERIES_MANIFEST_PATH` and `$PERSISTED_QUERIES_CACHE_SIZE`.
+    ///
+    /// The allowlist mode requires a manifest.
+    pub fn from_env() -> Result<Self, String> {
+        let mode = match env::var("PERSISTED_QUERIES_MODE") {
+            Ok(mode) => serde_json::from_value(serde_json::Value::String(mode.clone()))
+                .map_err(|_| format!("Persisted queries: mode `{}` is not supported.", mode))?,
+            Err(_) => PersistedQueryMode::default(),
+        };
+        let allowlist = match env::var_os("PERSISTED_QUERIES_MANIFEST_PATH") {
+            Some(path) => load_manifest(&path.to_string_lossy())?,
+            None if mode == PersistedQueryMode::Allowlist => {
+                return Err(
+                    "Persisted queries: allowlist mode requires `$PERSISTED_QUERIES_MANIFEST_PATH`."
+                        .to_string(),
+                );
+            }
+            None => HashMap::new(),
+        };
+        let cache_size = match env::var("PERSISTED_QUERIES_CACHE_SIZE") {
+            Ok(cache_size) => cache_size.parse().map_err(|_| {
+                format!(
+                    "Persisted queries: cache size `{}` is not a positive number.",
+                    cache_size
+                )
+            })?,
+            Err(_) => NonZeroUsize::new(DEFAULT_CACHE_SIZE).unwrap(),
+        };
+        let store = Arc::new(LruPersistedQueryStore::new(cache_size));
+        Ok(Self::new(mode, allowlist, store))
+    }
+}
+
+impl ExtensionFactory for PersistedQueries {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(PersistedQueriesExtension {
+            mode: self.mode,
+            allowlist: self.allowlist.clone(),
+            store: self.store.clone(),
+        })
+    }
+}
+
+struct PersistedQueriesExtension {
+    mode: PersistedQueryMode,
+    allowlist: Arc<HashMap<String, String>>,
+    store: Arc<dyn PersistedQueryStore>,
+}
+
+impl PersistedQueriesExtension {
+    /// Returns the query of a hash from the allowlist or, in automatic mode, from the store.
+    ///
+    /// * `sha256_hash` - Hex encoded SHA-256 hash of the query.
+    async fn lookup(&self, sha256_hash: &str) -> Option<String> {
+        if let Some(query) = self.allowlist.get(sha256_hash) {
+            return Some(query.clone());
+        }
+        match self.mode {
+            PersistedQueryMode::Automatic => self.store.get(sha256_hash).await,
+            PersistedQueryMode::Allowlist => None,
+        }
+    }
+
+    /// Resolves the query of a request referencing a persisted query.
+    ///
+    /// * `request` - GraphQL request, containing the query if the client registers it.
+    /// * `persisted_query` - `persistedQuery` extension of the request.
+    async fn resolve_persisted_query(
+        &self,
+        mut request: Request,
+        persisted_query: PersistedQuery,
+    ) -> ServerResult<Request> {
+        if persisted_query.version != 1 {
+            let message = format!(
+                "Persisted query version: `{}` is not supported, expected version 1.",
+                persisted_query.version
+            );
+            return Err(ServerError::new(message, None));
+        }
+        if request.query.is_empty() {
+            return match self.lookup(&persisted_query.sha256_hash).await {
+                Some(query) => {
+                    request.query = query;
+                    Ok(request)
+                }
+                None => Err(self.not_persisted_error()),
+            };
+        }
+        if sha256_hex(&request.query) != persisted_query.sha256_hash {
+            return Err(ServerError::new(
+                "Persisted query hash does not match the query.",
+                None,
+            ));
+        }
+        match self.mode {
+            PersistedQueryMode::Automatic => {
+                self.store
+                    .set(persisted_query.sha256_hash, request.query.clone())
+                    .await;
+                Ok(request)
+            }
+            PersistedQueryMode::Allowlist => self.check_allowlist(request),
+        }
+    }
+
+    /// Rejects a request if its query is not contained in the allowlist, unless it only queries the schema
+    /// of the service for the federation gateway.
+    ///
+    /// * `request` - GraphQL request containing the query.
+    fn check_allowlist(&self, request: Request) -> ServerResult<Request> {
+        if self.allowlist.contains_key(&sha256_hex(&request.query))
+            || is_service_schema_query(&request.query)
+        {
+            Ok(request)
+        } else {
+            Err(self.not_persisted_error())
+        }
+    }
+
+    /// Creates the error of an unknown persisted query, with the codes expected by Apollo clients.
+    fn not_persisted_error(&self) -> ServerError {
+        let (message, code) = match self.mode {
+            PersistedQueryMode::Automatic => {
+                ("PersistedQueryNotFound", "PERSISTED_QUERY_NOT_FOUND")
+            }
+            PersistedQueryMode::Allowlist => {
+                ("PersistedQueryNotInList", "PERSISTED_QUERY_NOT_IN_LIST")
+            }
+        };
+        let error = Error::new(message).extend_with(|_, extensions| extensions.set("code", code));
+        let mut server_error = ServerError::new(error.message, None);
+        server_error.extensions = error.extensions;
+        server_error
+    }
+}
+
+#[async_trait::async_trait]
+impl Extension for PersistedQueriesExtension {
+    /// Replaces the hash of a persisted query by its query before the request is parsed.
+    async fn prepare_request(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        mut request: Request,
+        next: NextPrepareRequest<'_>,
+    ) -> ServerResult<Request> {
+        let request = match request.extensions.remove(PERSISTED_QUERY_EXTENSION) {
+            Some(value) => {
+                let persisted_query: PersistedQuery = from_value(value).map_err(|_| {
+                    ServerError::new("Persisted query extension could not be parsed.", None)
+                })?;
+                self.resolve_persisted_query(request, persisted_query)
+                    .await?
+            }
+            None => match self.mode {
+                PersistedQueryMode::Automatic => request,
+                PersistedQueryMode::Allowlist => self.check_allowlist(request)?,
+            },
+        };
+        next.run(ctx, request).await
+    }
+}
+
+/// Returns the hex encoded SHA-256 hash of a query.
+///
+/// * `query` - GraphQL query.
+fn sha256_hex(query: &str) -> String {
+    format!("{:x}", Sha256::digest(query.as_bytes()))
+}
+
+/// Checks if a query only selects the schema of the service queried by the federation gateway,
+/// i.e. `_service { sdl }` and `__typename`.
+///
+/// * `query` - GraphQL query.
+fn is_service_schema_query(query: &str) -> bool {
+    let Ok(document) = parse_query(query) else {
+        return false;
+    };
+    document.operations.iter().all(|(_, operation)| {
+        operation.node.ty == OperationType::Query
+            && selects_only(&operation.node.selection_set.node, |field| {
+                match field.name.node.as_str() {
+                    "__typename" => field.selection_set.node.items.is_empty(),
+                    "_service" => {
+                        field.arguments.is_empty()
+                            && selects_only(&field.selection_set.node, |service_field| {
+                                SERVICE_FIELDS.contains(&service_field.name.node.as_str())
+                                    && service_field.selection_set.node.items.is_empty()
+                            })
+                    }
+                    _ => false,
+                }
+            })
+    })
+}
+
+/// Checks if a selection set only contains fields, without fragments, which all fulfill a condition.
+///
+/// * `selection_set` - Selection set to check.
+/// * `is_allowed` - Condition on each selected field.
+fn selects_only(selection_set: &SelectionSet, is_allowed: impl Fn(&Field) -> bool) -> bool {
+    selection_set
+        .items
+        .iter()
+        .all(|selection| match &selection.node {
+            Selection::Field(field) => is_allowed(&field.node),
+            _ => false,
+        })
+}
+
+/// Loads the queries of a persisted query manifest by their hash.
+///
+/// * `path` - Path of the manifest file.
+fn load_manifest(path: &str) -> Result<HashMap<String, String>, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|error| format!("Persisted queries: `{}` could not be read: {}", path, error))?;
+    parse_manifest(path, &content)
+}
+
+/// Parses the queries of a persisted query manifest by their hash.
+///
+/// Fails if the id of an operation is not the SHA-256 hash of its body.
+///
+/// * `path` - Path of the manifest file, used in error messages.
+/// * `content` - Content of the manifest file.
+fn parse_manifest(path: &str, content: &str) -> Result<HashMap<String, String>, String> {
+    let manifest: PersistedQueryManifest = serde_json::from_str(content).map_err(|error| {
+        format!(
+            "Persisted queries: `{}` could not be parsed: {}",
+            path, error
+        )
+    })?;
+    manifest
+        .operations
+        .into_iter()
+        .map(|operation| {
+            if sha256_hex(&operation.body) == operation.id {
+                Ok((operation.id, operation.body))
+            } else {
+                Err(format!(
+                    "Persisted queries: id `{}` is not the SHA-256 hash of its body.",
+                    operation.id
+                ))
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUERY: &str = "{ reviews { totalCount } }";
+
+    fn manifest(id: &str, body: &str) -> String {
+        serde_json::json!({
+            "format": "apollo-persisted-query-manifest",
+            "version": 1,
+            "operations": [{"id": id, "name": "Reviews", "type": "query", "body": body}],
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn accepts_manifest_with_matching_hashes() {
+        let content = manifest(&sha256_hex(QUERY), QUERY);
+        let allowlist = parse_manifest("manifest.json", &content).unwrap();
+        assert_eq!(allowlist.get(&sha256_hex(QUERY)), Some(&QUERY.to_string()));
+    }
+
+    #[test]
+    fn rejects_manifest_with_mismatching_hash() {
+        let content = manifest(&sha256_hex("{ __typename }"), QUERY);
+        let error = parse_manifest("manifest.json", &content).unwrap_err();
+        assert!(error.contains("is not the SHA-256 hash of its body"));
+    }
+
+    #[test]
+    fn rejects_unparsable_manifest() {
+        let error = parse_manifest("manifest.json", "{}").unwrap_err();
+        assert!(error.contains("could not be parsed"));
+    }
+
+    fn allowlist_extension() -> PersistedQueriesExtension {
+        PersistedQueriesExtension {
+            mode: PersistedQueryMode::Allowlist,
+            allowlist: Arc::new(HashMap::from([(sha256_hex(QUERY), QUERY.to_string())])),
+            store: Arc::new(LruPersistedQueryStore::new(NonZeroUsize::new(1).unwrap())),
+        }
+    }
+
+    #[test]
+    fn allowlist_executes_persisted_and_service_schema_queries() {
+        let extension = allowlist_extension();
+        assert!(extension.check_allowlist(Request::new(QUERY)).is_ok());
+        assert!(extension
+            .check_allowlist(Request::new("{ _service { sdl } }"))
+            .is_ok());
+        assert!(extension
+            .check_allowlist(Request::new("{ __typename }"))
+            .is_ok());
+    }
+
+    #[test]
+    fn allowlist_rejects_entities_queries_which_are_not_persisted() {
+        let extension = allowlist_extension();
+        let entities_query = "query($representations: [_Any!]!) { _entities(representations: $representations) { ... on User { reviews { nodes { body } } } } }";
+        let error = extension
+            .check_allowlist(Request::new(entities_query))
+            .unwrap_err();
+        assert_eq!(error.message, "PersistedQueryNotInList");
+    }
+
+    #[test]
+    fn recognizes_only_service_schema_queries() {
+        assert!(is_service_schema_query("{ _service { sdl } }"));
+        assert!(is_service_schema_query(
+            "query { __typename _service { sdl __typename } }"
+        ));
+        assert!(!is_service_schema_query(
+            "{ _entities(representations: []) { __typename } }"
+        ));
+        assert!(!is_service_schema_query(
+            "{ _service { sdl } reviews { totalCount } }"
+        ));
+        assert!(!is_service_schema_query(
+            "{ _service { ... on _Service { sdl } } }"
+        ));
+        assert!(!is_service_schema_query(
+            "{ ... on Query { _service { sdl } } }"
+        ));
+        assert!(!is_service_schema_query("mutation { _service { sdl } }"));
+        assert!(!is_service_schema_query("{ _service { "));
+    }
+}
@@ -0,0 +1,51 @@
+use std::{num::NonZeroUsize, sync::Mutex};
+
+use lru::LruCache;
+
+/// Store of queries registered by clients by their SHA-256 hash.
+///
+/// The in-memory store is separate for each replica, a shared store, e.g. backed by Redis,
+/// can implement this trait, so that clients only register a query once.
+#[async_trait::async_trait]
+pub trait PersistedQueryStore: Send + Sync {
+    /// Returns the query of a hash if it is stored.
+    ///
+    /// * `sha256_hash` - Hex encoded SHA-256 hash of the query.
+    async fn get(&self, sha256_hash: &str) -> Option<String>;
+
+    /// Stores the query of a hash.
+    ///
+    /// * `sha256_hash` - Hex encoded SHA-256 hash of the query.
+    /// * `query` - GraphQL query.
+    async fn set(&self, sha256_hash: String, query: String);
+}
+
+/// Queries held in the memory of this replica, evicting the least recently used query if full.
+pub struct LruPersistedQueryStore {
+    queries: Mutex<LruCache<String, String>>,
+}
+
+impl LruPersistedQueryStore {
+    /// Creates an empty store.
+    ///
+    /// * `capacity` - Maximum number of stored queries.
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        Self {
+            queries: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl PersistedQueryStore for LruPersistedQueryStore {
+    async fn get(&self, sha256_hash: &str) -> Option<String> {
+        let mut queries = self.queries.lock().ok()?;
+        queries.get(sha256_hash).cloned()
+    }
+
+    async fn set(&self, sha256_hash: String, query: String) {
+        if let Ok(mut queries) = self.queries.lock() {
+            queries.put(sha256_hash, query);
+        }
+    }
+}
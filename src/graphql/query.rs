@@ -1,22 +1,37 @@
-use async_graphql::{Context, Error, Object, Result};
+use async_graphql::{dataloader::DataLoader, Context, Error, Object, OutputType, Result};
 use std::any::type_name;
 
 use bson::{Document, Uuid};
 use mongodb::{bson::doc, options::FindOptions, Collection, Database};
 use mongodb_cursor_pagination::{error::CursorError, FindResult, PaginatedCursor};
-use serde::Deserialize;
+use serde::{de::DeserializeOwned, Deserialize};
 
+use crate::authorization::{authorize_user, authorized_user, permission::Permission};
+
+use super::loader::{
+    entity_loader::EntityLoader,
+    rating_summary_loader::rating_summary_stages,
+    review_connection_loader::{load_all_reviews, ReviewPage},
+};
 use super::model::{
     connection::{
-        base_connection::{BaseConnection, FindResultWrapper},
+        base_connection::{aggregate_connection, BaseConnection, FindResultWrapper},
+        failed_event_connection::FailedEventConnection,
+        product_connection::ProductConnection,
+        product_variant_connection::ProductVariantConnection,
         review_connection::ReviewConnection,
+        user_connection::UserConnection,
     },
-    order_datatypes::ReviewOrderInput,
+    failed_event::FailedEvent,
+    order_datatypes::{CommonOrderField, CommonOrderInput, ReviewOrderInput},
     product::Product,
     product_variant::ProductVariant,
     review::Review,
     user::User,
 };
+use super::query_limits::{connection_complexity, page_size};
+use super::response_cache::RESPONSE_CACHE;
+use super::user_data_export::export_user_data;
 
 /// Describes GraphQL review queries.
 pub struct Query;
@@ -30,9 +45,8 @@ impl Query {
         ctx: &Context<'a>,
         #[graphql(desc = "UUID of user to retrieve.")] id: Uuid,
     ) -> Result<Option<User>> {
-        let db_client = ctx.data::<Database>()?;
-        let collection: Collection<User> = db_client.collection::<User>("users");
-        query_object_optional(&collection, id).await
+        let user_loader = ctx.data::<DataLoader<EntityLoader<User>>>()?;
+        user_loader.load_one(id).await
     }
 
     /// Entity resolver for product of specific UUID.
@@ -42,9 +56,8 @@ impl Query {
         ctx: &Context<'a>,
         #[graphql(desc = "UUID of product to retrieve.")] id: Uuid,
     ) -> Result<Option<Product>> {
-        let db_client = ctx.data::<Database>()?;
-        let collection: Collection<Product> = db_client.collection::<Product>("products");
-        query_object_optional(&collection, id).await
+        let product_loader = ctx.data::<DataLoader<EntityLoader<Product>>>()?;
+        product_loader.load_one(id).await
     }
 
     /// Entity resolver for product variant of specific UUID.
@@ -54,60 +67,244 @@ impl Query {
         ctx: &Context<'a>,
         #[graphql(desc = "UUID of product variant to retrieve.")] id: Uuid,
     ) -> Result<Option<ProductVariant>> {
-        let db_client = ctx.data::<Database>()?;
-        let collection: Collection<ProductVariant> =
-            db_client.collection::<ProductVariant>("product_variants");
-        query_object_optional(&collection, id).await
+        let product_variant_loader = ctx.data::<DataLoader<EntityLoader<ProductVariant>>>()?;
+        product_variant_loader.load_one(id).await
     }
 
     /// Retrieves all reviews.
+    ///
+    /// Hidden reviews are only retrieved for their author and users with the `review:moderate` permission.
+    #[graphql(complexity = "connection_complexity(first, child_complexity)")]
     async fn reviews<'a>(
         &self,
         ctx: &Context<'a>,
-        #[graphql(desc = "Describes that the `first` N reviews should be retrieved.")]
+        #[graphql(
+            desc = "Describes that the `first` N reviews should be retrieved, by default 20 and at most 100."
+        )]
         first: Option<u32>,
         #[graphql(desc = "Describes how many reviews should be skipped at the beginning.")]
         skip: Option<u64>,
-        #[graphql(desc = "Specifies the order in which reviews are retrieved.")] order_by: Option<
-            ReviewOrderInput,
-        >,
+        #[graphql(
+            desc = "Specifies the order in which reviews are retrieved, later order inputs break ties of earlier ones."
+        )]
+        order_by: Option<Vec<ReviewOrderInput>>,
+        #[graphql(desc = "Only retrieves reviews of product variants in this product category.")]
+        product_category: Option<String>,
     ) -> Result<ReviewConnection> {
+        let mut page = ReviewPage::new(ctx, first, skip, order_by)?;
+        page.product_category = product_category;
+        let db_client = ctx.data::<Database>()?;
+        load_all_reviews(db_client, &page).await
+    }
+
+    /// Retrieves review of specific UUID.
+    ///
+    /// Hidden reviews are only retrieved for their author and users with the `review:moderate` permission.
+    #[graphql(cache_control(max_age = 60))]
+    async fn review<'a>(
+        &self,
+        ctx: &Context<'a>,
+        #[graphql(desc = "UUID of review to retrieve.")] id: Uuid,
+    ) -> Result<Option<Review>> {
         let db_client = ctx.data::<Database>()?;
         let collection: Collection<Review> = db_client.collection::<Review>("reviews");
-        let review_order = order_by.unwrap_or_default();
-        let sorting_doc = doc! {review_order.field.unwrap_or_default().as_str(): i32::from(review_order.direction.unwrap_or_default())};
+        let maybe_review = RESPONSE_CACHE
+            .review(&id, query_object_optional(&collection, id))
+            .await?;
+        Ok(maybe_review.filter(|review| review.is_visible_to(ctx)))
+    }
+
+    /// Exports all review data of the authorized user as downloadable JSON document.
+    async fn export_my_review_data<'a>(&self, ctx: &Context<'a>) -> Result<String> {
+        let authorized_user_header = authorized_user(ctx)?;
+        let db_client = ctx.data::<Database>()?;
+        export_user_data(db_client, authorized_user_header.id).await
+    }
+
+    /// Exports all review data of a user as downloadable JSON document.
+    ///
+    /// Requires the `review:export` permission for data of other users.
+    async fn export_user_review_data<'a>(
+        &self,
+        ctx: &Context<'a>,
+        #[graphql(desc = "UUID of user to export the review data of.")] user_id: Uuid,
+    ) -> Result<String> {
+        authorize_user(ctx, Some(user_id), Permission::ExportReviews)?;
+        let db_client = ctx.data::<Database>()?;
+        export_user_data(db_client, user_id).await
+    }
+
+    /// Retrieves events which could not be processed, most recent failures first.
+    ///
+    /// Requires the `event:manage` permission.
+    #[graphql(complexity = "connection_complexity(first, child_complexity)")]
+    async fn failed_events<'a>(
+        &self,
+        ctx: &Context<'a>,
+        #[graphql(
+            desc = "Describes that the `first` N failed events should be retrieved, by default 20 and at most 100."
+        )]
+        first: Option<u32>,
+        #[graphql(desc = "Describes how many failed events should be skipped at the beginning.")]
+        skip: Option<u64>,
+    ) -> Result<FailedEventConnection> {
+        authorize_user(ctx, None, Permission::ManageEvents)?;
+        let db_client = ctx.data::<Database>()?;
+        let collection: Collection<FailedEvent> =
+            db_client.collection::<FailedEvent>("failed_events");
         let find_options = FindOptions::builder()
             .skip(skip)
-            .limit(first.map(|definitely_first| i64::from(definitely_first)))
-            .sort(sorting_doc)
+            .limit(page_size(first)?)
+            .sort(doc! {"last_failed_at": -1})
             .build();
         let document_collection = collection.clone_with_type::<Document>();
-        let maybe_find_results: Result<FindResult<Review>, CursorError> =
+        let maybe_find_results: Result<FindResult<FailedEvent>, CursorError> =
             PaginatedCursor::new(Some(find_options.clone()), None, None)
                 .find(&document_collection, None)
                 .await;
         match maybe_find_results {
             Ok(find_results) => {
                 let find_result_wrapper = FindResultWrapper(find_results);
-                let connection = Into::<BaseConnection<Review>>::into(find_result_wrapper);
-                Ok(Into::<ReviewConnection>::into(connection))
+                let connection = Into::<BaseConnection<FailedEvent>>::into(find_result_wrapper);
+                Ok(Into::<FailedEventConnection>::into(connection))
             }
-            Err(_) => return Err(Error::new("Retrieving reviews failed in MongoDB.")),
+            Err(_) => Err(Error::new("Retrieving failed events failed in MongoDB.")),
         }
     }
 
-    /// Retrieves review of specific UUID.
-    async fn review<'a>(
+    /// Retrieves mirrored users, e.g. ordered by their number of visible reviews.
+    ///
+    /// Requires the `entity:list` permission.
+    #[graphql(complexity = "connection_complexity(first, child_complexity)")]
+    async fn users<'a>(
         &self,
         ctx: &Context<'a>,
-        #[graphql(desc = "UUID of review to retrieve.")] id: Uuid,
-    ) -> Result<Option<Review>> {
+        #[graphql(
+            desc = "Describes that the `first` N users should be retrieved, by default 20 and at most 100."
+        )]
+        first: Option<u32>,
+        #[graphql(desc = "Describes how many users should be skipped at the beginning.")]
+        skip: Option<u64>,
+        #[graphql(desc = "Specifies the order in which users are retrieved.")] order_by: Option<
+            CommonOrderInput,
+        >,
+    ) -> Result<UserConnection> {
+        authorize_user(ctx, None, Permission::ListEntities)?;
         let db_client = ctx.data::<Database>()?;
-        let collection: Collection<Review> = db_client.collection::<Review>("reviews");
-        query_object_optional(&collection, id).await
+        let collection = db_client.collection::<Document>("users");
+        let connection =
+            list_entities::<User>(&collection, "user._id", first, skip, order_by).await?;
+        Ok(connection.into())
+    }
+
+    /// Retrieves mirrored products, e.g. ordered by their number of 1-star reviews.
+    ///
+    /// Requires the `entity:list` permission.
+    #[graphql(complexity = "connection_complexity(first, child_complexity)")]
+    async fn products<'a>(
+        &self,
+        ctx: &Context<'a>,
+        #[graphql(
+            desc = "Describes that the `first` N products should be retrieved, by default 20 and at most 100."
+        )]
+        first: Option<u32>,
+        #[graphql(desc = "Describes how many products should be skipped at the beginning.")]
+        skip: Option<u64>,
+        #[graphql(desc = "Specifies the order in which products are retrieved.")] order_by: Option<
+            CommonOrderInput,
+        >,
+    ) -> Result<ProductConnection> {
+        authorize_user(ctx, None, Permission::ListEntities)?;
+        let db_client = ctx.data::<Database>()?;
+        let collection = db_client.collection::<Document>("products");
+        let connection = list_entities::<Product>(
+            &collection,
+            "product_variant.product_id",
+            first,
+            skip,
+            order_by,
+        )
+        .await?;
+        Ok(connection.into())
+    }
+
+    /// Retrieves mirrored product variants, e.g. ordered by their average rating.
+    ///
+    /// Requires the `entity:list` permission.
+    #[graphql(complexity = "connection_complexity(first, child_complexity)")]
+    async fn product_variants<'a>(
+        &self,
+        ctx: &Context<'a>,
+        #[graphql(
+            desc = "Describes that the `first` N product variants should be retrieved, by default 20 and at most 100."
+        )]
+        first: Option<u32>,
+        #[graphql(
+            desc = "Describes how many product variants should be skipped at the beginning."
+        )]
+        skip: Option<u64>,
+        #[graphql(desc = "Specifies the order in which product variants are retrieved.")]
+        order_by: Option<CommonOrderInput>,
+    ) -> Result<ProductVariantConnection> {
+        authorize_user(ctx, None, Permission::ListEntities)?;
+        let db_client = ctx.data::<Database>()?;
+        let collection = db_client.collection::<Document>("product_variants");
+        let connection = list_entities::<ProductVariant>(
+            &collection,
+            "product_variant._id",
+            first,
+            skip,
+            order_by,
+        )
+        .await?;
+        Ok(connection.into())
     }
 }
 
+/// Shared function to list entities: `T`, ordered by their UUID or the rating summary of their visible reviews.
+///
+/// Rating summaries are only computed if entities are ordered by them, ties are broken by UUID.
+/// Fails if reviews of one rating are counted for another field than the review count.
+///
+/// * `collection` - MongoDB collection of the entities.
+/// * `review_field` - Field of a review document referencing the entities.
+/// * `first` - Number of entities requested by the `first` argument.
+/// * `skip` - Number of entities to skip at the beginning.
+/// * `order_by` - Order in which entities are retrieved.
+async fn list_entities<T: OutputType + DeserializeOwned>(
+    collection: &Collection<Document>,
+    review_field: &str,
+    first: Option<u32>,
+    skip: Option<u64>,
+    order_by: Option<CommonOrderInput>,
+) -> Result<BaseConnection<T>> {
+    let common_order = order_by.unwrap_or_default();
+    let order_field = common_order.field.unwrap_or_default();
+    if common_order.rating.is_some() && order_field != CommonOrderField::ReviewCount {
+        return Err(Error::new(
+            "Order input `rating` is only supported when ordering by `REVIEW_COUNT`.",
+        ));
+    }
+    let direction = i32::from(common_order.direction.unwrap_or_default());
+    let pipeline = match order_field {
+        CommonOrderField::Id => vec![doc! {"$sort": {"_id": direction}}],
+        _ => {
+            let mut pipeline = rating_summary_stages(review_field, common_order.rating);
+            pipeline.extend([
+                doc! {"$sort": {order_field.as_str(): direction, "_id": 1}},
+                doc! {"$replaceRoot": {"newRoot": "$entity"}},
+            ]);
+            pipeline
+        }
+    };
+    aggregate_connection(collection, pipeline, skip.unwrap_or(0), page_size(first)?)
+        .await
+        .map_err(|_| {
+            let message = format!("Retrieving {} failed in MongoDB.", collection.name());
+            Error::new(message)
+        })
+}
+
 /// Shared function to query an object: `T` from a MongoDB collection of object: `T`.
 ///
 /// * `connection` - MongoDB database connection.
@@ -147,4 +344,4 @@ pub async fn query_object_optional<T: for<'a> Deserialize<'a> + Unpin + Send + S
             Err(Error::new(message))
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,67 @@
+use async_graphql::{Error, Result};
+
+/// Maximum nesting depth of GraphQL queries.
+pub const MAX_QUERY_DEPTH: usize = 12;
+
+/// Maximum complexity of GraphQL queries, see `connection_complexity` and `RATING_SUMMARY_COMPLEXITY`.
+pub const MAX_QUERY_COMPLEXITY: usize = 5000;
+
+/// Number of entities retrieved by a connection if `first` is not set.
+pub const DEFAULT_PAGE_SIZE: u32 = 20;
+
+/// Maximum number of entities retrieved by a connection.
+pub const MAX_PAGE_SIZE: u32 = 100;
+
+/// Maximum number of reviews a review connection can skip.
+pub const MAX_REVIEW_SKIP: u64 = 10_000;
+
+/// Complexity of the base cost of a connection, independent of the retrieved entities.
+pub const CONNECTION_COMPLEXITY: usize = 5;
+
+/// Complexity of fields aggregating all reviews of an entity, e.g. `averageRating`, `reviewCount`, `reviewerStatistics` and `ratingTrend`.
+pub const RATING_SUMMARY_COMPLEXITY: usize = 10;
+
+/// Returns the number of entities a connection retrieves.
+///
+/// Fails if `first` exceeds `MAX_PAGE_SIZE`.
+///
+/// * `first` - Number of entities requested by the `first` argument.
+pub fn page_size(first: Option<u32>) -> Result<i64> {
+    match first {
+        Some(definitely_first) if definitely_first > MAX_PAGE_SIZE => {
+            let message = format!(
+                "Page size: `{}` exceeds the maximum page size: `{}`.",
+                definitely_first, MAX_PAGE_SIZE
+            );
+            Err(Error::new(message))
+        }
+        _ => Ok(i64::from(first.unwrap_or(DEFAULT_PAGE_SIZE))),
+    }
+}
+
+/// Returns the number of reviews a review connection skips.
+///
+/// Fails if `skip` exceeds `MAX_REVIEW_SKIP`, since review connections collect all skipped reviews per parent.
+///
+/// * `skip` - Number of reviews requested to skip by the `skip` argument.
+pub fn review_skip(skip: Option<u64>) -> Result<u64> {
+    match skip {
+        Some(definitely_skip) if definitely_skip > MAX_REVIEW_SKIP => {
+            let message = format!(
+                "Skip: `{}` exceeds the maximum number of skipped reviews: `{}`.",
+                definitely_skip, MAX_REVIEW_SKIP
+            );
+            Err(Error::new(message))
+        }
+        _ => Ok(skip.unwrap_or(0)),
+    }
+}
+
+/// Calculates the complexity of a connection field, the complexity of its selection multiplied by the page size.
+///
+/// * `first` - Number of entities requested by the `first` argument.
+/// * `child_complexity` - Complexity of the selection of the connection.
+pub fn connection_complexity(first: Option<u32>, child_complexity: usize) -> usize {
+    let page_size = first.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE) as usize;
+    CONNECTION_COMPLEXITY + page_size * child_complexity
+}
@@ -0,0 +1,474 @@
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+use async_graphql::{
+    extensions::{Extension, ExtensionContext, ExtensionFactory, NextResolve, ResolveInfo},
+    Error, ErrorExtensions, PathSegment, ServerError, ServerResult, Value,
+};
+use axum::http::HeaderMap;
+use log::warn;
+use once_cell::sync::Lazy;
+use opentelemetry::KeyValue;
+use serde::Deserialize;
+
+use crate::{authorization::AuthorizedUserHeader, telemetry::metrics::METRICS};
+
+use store::{Acquisition, RateLimitStore, TokenBucketLimit};
+
+pub mod store;
+
+/// Header containing the client IP address if the request was forwarded by a proxy.
+const FORWARDED_FOR_HEADER: &str = "X-Forwarded-For";
+
+/// Error code of GraphQL errors for rate limited operations.
+const RATE_LIMITED_CODE: &str = "RATE_LIMITED";
+
+/// IP addresses of proxies trusted to append to the `X-Forwarded-For` header,
+/// read from the comma-separated list at `$TRUSTED_PROXIES`.
+pub static TRUSTED_PROXIES: Lazy<HashSet<IpAddr>> =
+    Lazy::new(|| load_trusted_proxies().unwrap_or_else(|message| panic!("{}", message)));
+
+/// IP address of the client sending a request.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClientIp(pub IpAddr);
+
+impl ClientIp {
+    /// Determines the client IP address as the rightmost address which is not a trusted proxy.
+    ///
+    /// The `X-Forwarded-For` header is only honoured if the connection comes from a trusted proxy,
+    /// since clients can prepend arbitrary entries to it.
+    ///
+    /// * `headers` - Header map containing headers of request.
+    /// * `remote_addr` - Address of the connection.
+    /// * `trusted_proxies` - IP addresses of trusted proxies.
+    pub fn from_request(
+        headers: &HeaderMap,
+        remote_addr: SocketAddr,
+        trusted_proxies: &HashSet<IpAddr>,
+    ) -> Self {
+        let mut client_ip = remote_addr.ip();
+        if !trusted_proxies.contains(&client_ip) {
+            return Self(client_ip);
+        }
+        let forwarded_ips: Vec<&str> = headers
+            .get_all(FORWARDED_FOR_HEADER)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .collect();
+        for forwarded_ip in forwarded_ips.into_iter().rev() {
+            match forwarded_ip.trim().parse() {
+                Ok(ip) => {
+                    client_ip = ip;
+                    if !trusted_proxies.contains(&ip) {
+                        break;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+        Self(client_ip)
+    }
+}
+
+/// Loads the IP addresses of trusted proxies, no proxy is trusted if `$TRUSTED_PROXIES` is not set.
+fn load_trusted_proxies() -> Result<HashSet<IpAddr>, String> {
+    match env::var("TRUSTED_PROXIES") {
+        Ok(trusted_proxies) => trusted_proxies
+            .split(',')
+            .map(str::trim)
+            .filter(|trusted_proxy| !trusted_proxy.is_empty())
+            .map(|trusted_proxy| {
+                trusted_proxy.parse().map_err(|error| {
+                    format!(
+                        "Trusted proxy: `{}` could not be parsed: {}",
+                        trusted_proxy, error
+                    )
+                })
+            })
+            .collect(),
+        Err(_) => Ok(HashSet::new()),
+    }
+}
+
+/// Limit of a bucket in the configuration, either a token bucket limit or `"disabled"`.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(try_from = "RawLimitSetting")]
+pub enum LimitSetting {
+    /// Calls are limited by a token bucket.
+    Limited(TokenBucketLimit),
+    /// Calls are not limited, also if the default limits set a limit.
+    Disabled,
+}
+
+/// Representation of `LimitSetting` in the configuration file.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawLimitSetting {
+    Limited(TokenBucketLimit),
+    Keyword(String),
+}
+
+impl TryFrom<RawLimitSetting> for LimitSetting {
+    type Error = String;
+
+    fn try_from(value: RawLimitSetting) -> Result<Self, Self::Error> {
+        match value {
+            RawLimitSetting::Limited(limit) => Ok(LimitSetting::Limited(limit)),
+            RawLimitSetting::Keyword(keyword) if keyword == "disabled" => {
+                Ok(LimitSetting::Disabled)
+            }
+            RawLimitSetting::Keyword(keyword) => Err(format!(
+                "Limit: `{}` is neither a token bucket limit nor `disabled`.",
+                keyword
+            )),
+        }
+    }
+}
+
+/// Token bucket limits of an operation, an unset limit falls back to the default limits.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OperationRateLimits {
+    /// Limit per authorized user.
+    pub per_user: Option<LimitSetting>,
+    /// Limit per client IP address.
+    pub per_ip: Option<LimitSetting>,
+}
+
+/// Token bucket limits applying to the calls of an operation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct EffectiveRateLimits {
+    per_user: Option<TokenBucketLimit>,
+    per_ip: Option<TokenBucketLimit>,
+}
+
+/// Rate limits of mutations, read from the JSON file at `$RATE_LIMITS_PATH` or defaulting to
+/// `RateLimitConfig::default`.
+///
+/// Limits of an operation fall back to the default limits if they are not set,
+/// and can turn off a default limit with `"disabled"`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RateLimitConfig {
+    /// Limits of mutations without own limits.
+    #[serde(default)]
+    pub default: OperationRateLimits,
+    /// Limits by name of mutation, e.g. `createReview`.
+    #[serde(default)]
+    pub operations: HashMap<String, OperationRateLimits>,
+}
+
+impl Default for RateLimitConfig {
+    /// Allows bursts of 10 calls per user for each mutation, refilled at 10 calls per minute.
+    ///
+    /// Calls are not limited per IP address by default, since without `$TRUSTED_PROXIES`
+    /// all calls forwarded by the gateway share the IP address of the gateway.
+    fn default() -> Self {
+        Self {
+            default: OperationRateLimits {
+                per_user: Some(LimitSetting::Limited(TokenBucketLimit {
+                    capacity: 10,
+                    refill_per_minute: 10,
+                })),
+                per_ip: None,
+            },
+            operations: HashMap::new(),
+        }
+    }
+}
+
+impl RateLimitConfig {
+    /// Loads the rate limits from the JSON file at `$RATE_LIMITS_PATH` if set.
+    ///
+    /// The configuration file contains default and per operation limits,
+    /// e.g. `{"operations": {"createReview": {"perUser": {"capacity": 3, "refillPerMinute": 1}}}}`.
+    pub fn from_env() -> Result<Self, String> {
+        match env::var_os("RATE_LIMITS_PATH") {
+            Some(path) => {
+                let content = fs::read_to_string(&path).map_err(|error| {
+                    format!(
+                        "Rate limits: `{}` could not be read: {}",
+                        path.to_string_lossy(),
+                        error
+                    )
+                })?;
+                serde_json::from_str(&content).map_err(|error| {
+                    format!(
+                        "Rate limits: `{}` could not be parsed: {}",
+                        path.to_string_lossy(),
+                        error
+                    )
+                })
+            }
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Returns the limits applying to an operation.
+    ///
+    /// * `operation` - Name of the mutation.
+    fn limits_of(&self, operation: &str) -> EffectiveRateLimits {
+        let operation_limits = self.operations.get(operation).copied().unwrap_or_default();
+        EffectiveRateLimits {
+            per_user: effective_limit(operation_limits.per_user, self.default.per_user),
+            per_ip: effective_limit(operation_limits.per_ip, self.default.per_ip),
+        }
+    }
+}
+
+/// Returns the limit of an operation, falling back to the default limit if the operation does not set one.
+///
+/// * `operation_limit` - Limit set for the operation.
+/// * `default_limit` - Limit set for all operations.
+fn effective_limit(
+    operation_limit: Option<LimitSetting>,
+    default_limit: Option<LimitSetting>,
+) -> Option<TokenBucketLimit> {
+    match operation_limit.or(default_limit) {
+        Some(LimitSetting::Limited(limit)) => Some(limit),
+        Some(LimitSetting::Disabled) | None => None,
+    }
+}
+
+/// GraphQL extension limiting the calls of mutations per user and per client IP address with token buckets.
+///
+/// Rejected calls fail with a GraphQL error with the code `RATE_LIMITED`
+/// and the seconds until the next call is allowed as `retryAfter` extension.
+pub struct RateLimit {
+    config: Arc<RateLimitConfig>,
+    store: Arc<dyn RateLimitStore>,
+}
+
+impl RateLimit {
+    /// Creates the extension.
+    ///
+    /// * `config` - Rate limits of mutations.
+    /// * `store` - Store of the token buckets.
+    pub fn new(config: RateLimitConfig, store: Arc<dyn RateLimitStore>) -> Self {
+        Self {
+            config: Arc::new(config),
+            store,
+        }
+    }
+}
+
+impl ExtensionFactory for RateLimit {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(RateLimitExtension {
+            config: self.config.clone(),
+            store: self.store.clone(),
+        })
+    }
+}
+
+struct RateLimitExtension {
+    config: Arc<RateLimitConfig>,
+    store: Arc<dyn RateLimitStore>,
+}
+
+impl RateLimitExtension {
+    /// Takes a token from each bucket if all of them have one available.
+    ///
+    /// Returns the index of the empty bucket with the longest wait and the seconds until every bucket has a token.
+    /// Fails open if the store is unavailable, so that an outage of a shared store does not block all mutations.
+    ///
+    /// * `buckets` - Keys of the buckets with their limits.
+    async fn acquire(&self, buckets: &[(String, TokenBucketLimit)]) -> Option<(usize, u64)> {
+        match self.store.acquire(buckets).await {
+            Ok(Acquisition::Granted) => None,
+            Ok(Acquisition::Limited {
+                bucket_index,
+                retry_after,
+            }) => Some((bucket_index, retry_after.as_secs_f64().ceil() as u64)),
+            Err(error) => {
+                warn!("Rate limits could not be checked: {}", error);
+                None
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Extension for RateLimitExtension {
+    /// Checks the rate limits before resolving a mutation.
+    async fn resolve(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        info: ResolveInfo<'_>,
+        next: NextResolve<'_>,
+    ) -> ServerResult<Option<Value>> {
+        let is_mutation =
+            ctx.schema_env.registry.mutation_type.as_deref() == Some(info.parent_type);
+        if !is_mutation {
+            return next.run(ctx, info).await;
+        }
+        let limits = self.config.limits_of(info.name);
+        let mut scopes = Vec::new();
+        let mut buckets = Vec::new();
+        if let Some(definitely_limit) = limits.per_user
+            && let Some(authorized_user_header) = ctx.data_opt::<AuthorizedUserHeader>()
+        {
+            let key = format!("{}:user:{}", info.name, authorized_user_header.id);
+            scopes.push("user");
+            buckets.push((key, definitely_limit));
+        }
+        if let Some(definitely_limit) = limits.per_ip
+            && let Some(ClientIp(ip)) = ctx.data_opt::<ClientIp>()
+        {
+            let key = format!("{}:ip:{}", info.name, ip);
+            scopes.push("ip");
+            buckets.push((key, definitely_limit));
+        }
+        if buckets.is_empty() {
+            return next.run(ctx, info).await;
+        }
+        if let Some((bucket_index, retry_after)) = self.acquire(&buckets).await {
+            METRICS.rate_limited_calls.add(
+                1,
+                &[
+                    KeyValue::new("operation", info.name.to_string()),
+                    KeyValue::new("scope", scopes[bucket_index]),
+                ],
+            );
+            return Err(rate_limited_error(&info, retry_after));
+        }
+        next.run(ctx, info).await
+    }
+}
+
+/// Creates the error of a rate limited operation.
+///
+/// * `info` - Resolve info of the rejected mutation.
+/// * `retry_after` - Seconds until the next call is allowed.
+fn rate_limited_error(info: &ResolveInfo<'_>, retry_after: u64) -> ServerError {
+    let message = format!(
+        "Rate limit exceeded for operation: `{}`. Retry after {} seconds.",
+        info.name, retry_after
+    );
+    let error = Error::new(message).extend_with(|_, extensions| {
+        extensions.set("code", RATE_LIMITED_CODE);
+        extensions.set("retryAfter", retry_after);
+    });
+    let mut server_error = ServerError::new(error.message, None);
+    server_error.extensions = error.extensions;
+    server_error.path = info
+        .path_node
+        .to_string_vec()
+        .into_iter()
+        .map(PathSegment::Field)
+        .collect();
+    server_error
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    fn headers_forwarded_for(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(FORWARDED_FOR_HEADER, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    fn remote_addr(value: &str) -> SocketAddr {
+        SocketAddr::new(ip(value), 443)
+    }
+
+    #[test]
+    fn ignores_forwarded_for_of_untrusted_connection() {
+        let headers = headers_forwarded_for("203.0.113.7");
+        let client_ip =
+            ClientIp::from_request(&headers, remote_addr("198.51.100.1"), &HashSet::new());
+        assert_eq!(client_ip, ClientIp(ip("198.51.100.1")));
+    }
+
+    #[test]
+    fn uses_rightmost_untrusted_forwarded_for_entry() {
+        let headers = headers_forwarded_for("1.2.3.4, 203.0.113.7, 10.0.0.2");
+        let trusted_proxies = HashSet::from([ip("10.0.0.1"), ip("10.0.0.2")]);
+        let client_ip = ClientIp::from_request(&headers, remote_addr("10.0.0.1"), &trusted_proxies);
+        assert_eq!(client_ip, ClientIp(ip("203.0.113.7")));
+    }
+
+    #[test]
+    fn uses_leftmost_entry_if_all_entries_are_trusted() {
+        let headers = headers_forwarded_for("10.0.0.3, 10.0.0.2");
+        let trusted_proxies = HashSet::from([ip("10.0.0.1"), ip("10.0.0.2"), ip("10.0.0.3")]);
+        let client_ip = ClientIp::from_request(&headers, remote_addr("10.0.0.1"), &trusted_proxies);
+        assert_eq!(client_ip, ClientIp(ip("10.0.0.3")));
+    }
+
+    #[test]
+    fn stops_at_unparsable_forwarded_for_entry() {
+        let headers = headers_forwarded_for("203.0.113.7, unknown, 10.0.0.2");
+        let trusted_proxies = HashSet::from([ip("10.0.0.1"), ip("10.0.0.2")]);
+        let client_ip = ClientIp::from_request(&headers, remote_addr("10.0.0.1"), &trusted_proxies);
+        assert_eq!(client_ip, ClientIp(ip("10.0.0.2")));
+    }
+
+    #[test]
+    fn uses_connection_of_trusted_proxy_without_forwarded_for() {
+        let trusted_proxies = HashSet::from([ip("10.0.0.1")]);
+        let client_ip =
+            ClientIp::from_request(&HeaderMap::new(), remote_addr("10.0.0.1"), &trusted_proxies);
+        assert_eq!(client_ip, ClientIp(ip("10.0.0.1")));
+    }
+
+    const USER_LIMIT: TokenBucketLimit = TokenBucketLimit {
+        capacity: 3,
+        refill_per_minute: 1,
+    };
+
+    #[test]
+    fn does_not_limit_per_ip_by_default() {
+        let limits = RateLimitConfig::default().limits_of("createReview");
+        assert!(limits.per_user.is_some());
+        assert_eq!(limits.per_ip, None);
+    }
+
+    #[test]
+    fn operation_limits_fall_back_to_default_limits() {
+        let config: RateLimitConfig = serde_json::from_str(
+            r#"{
+                "default": {"perUser": {"capacity": 10, "refillPerMinute": 10}},
+                "operations": {"createReview": {"perIp": {"capacity": 3, "refillPerMinute": 1}}}
+            }"#,
+        )
+        .unwrap();
+        let limits = config.limits_of("createReview");
+        assert_eq!(limits.per_user.map(|limit| limit.capacity), Some(10));
+        assert_eq!(limits.per_ip, Some(USER_LIMIT));
+    }
+
+    #[test]
+    fn disables_default_limit_for_operation() {
+        let config: RateLimitConfig = serde_json::from_str(
+            r#"{
+                "default": {"perUser": {"capacity": 3, "refillPerMinute": 1}},
+                "operations": {"voteReview": {"perUser": "disabled"}}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(config.limits_of("voteReview").per_user, None);
+        assert_eq!(config.limits_of("createReview").per_user, Some(USER_LIMIT));
+    }
+
+    #[test]
+    fn rejects_unknown_limit_keyword() {
+        let result = serde_json::from_str::<RateLimitConfig>(
+            r#"{"operations": {"voteReview": {"perUser": "off"}}}"#,
+        );
+        assert!(result.is_err());
+    }
+}
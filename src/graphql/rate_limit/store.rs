@@ -0,0 +1,345 @@
+use std::{
+    error::Error,
+    num::NonZeroUsize,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use lru::LruCache;
+use serde::Deserialize;
+
+/// Maximum number of buckets tracked by the in-memory store.
+const MAX_TRACKED_BUCKETS: usize = 10_000;
+
+/// Limit of a token bucket.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenBucketLimit {
+    /// Maximum number of tokens, i.e. the number of calls allowed in a burst.
+    pub capacity: u32,
+    /// Number of tokens added per minute.
+    pub refill_per_minute: u32,
+}
+
+impl TokenBucketLimit {
+    /// Number of tokens added per second.
+    fn refill_per_second(&self) -> f64 {
+        f64::from(self.refill_per_minute) / 60.0
+    }
+}
+
+/// Outcome of taking a token from each of a set of buckets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Acquisition {
+    /// A token was taken from every bucket, the call is allowed.
+    Granted,
+    /// At least one bucket is empty, the call is rejected and no token is taken.
+    Limited {
+        /// Index of the empty bucket with the longest wait.
+        bucket_index: usize,
+        /// Duration until every bucket has a token available.
+        retry_after: Duration,
+    },
+}
+
+/// Store of token buckets.
+///
+/// The in-memory store limits each replica separately, a shared store, e.g. backed by Redis,
+/// can implement this trait to limit calls across replicas.
+#[async_trait::async_trait]
+pub trait RateLimitStore: Send + Sync {
+    /// Refills the buckets of keys and takes a token from each of them if all have one available.
+    ///
+    /// Either takes a token from every bucket or from none, so that a call rejected by one limit
+    /// does not use up the tokens of the other limits.
+    ///
+    /// * `buckets` - Keys of the buckets, e.g. containing operation and user UUID, with their limits,
+    ///   a missing bucket starts full.
+    async fn acquire(
+        &self,
+        buckets: &[(String, TokenBucketLimit)],
+    ) -> Result<Acquisition, Box<dyn Error + Send + Sync>>;
+}
+
+/// Token bucket of a key.
+#[derive(Debug)]
+struct TokenBucket {
+    /// Number of available tokens.
+    tokens: f64,
+    /// Timestamp of the last refill.
+    last_refill: Instant,
+    /// Limit of the bucket at the last refill.
+    limit: TokenBucketLimit,
+}
+
+impl TokenBucket {
+    /// Adds the tokens accumulated since the last refill.
+    ///
+    /// * `now` - Timestamp of the refill.
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.limit.refill_per_second())
+            .min(f64::from(self.limit.capacity));
+        self.last_refill = now;
+    }
+}
+
+/// Token buckets held in the memory of this replica, evicting the least recently used bucket if full.
+#[derive(Debug)]
+pub struct InMemoryRateLimitStore {
+    buckets: Mutex<LruCache<String, TokenBucket>>,
+}
+
+impl Default for InMemoryRateLimitStore {
+    /// Creates an empty store tracking at most `MAX_TRACKED_BUCKETS` buckets.
+    fn default() -> Self {
+        Self::new(NonZeroUsize::new(MAX_TRACKED_BUCKETS).unwrap())
+    }
+}
+
+impl InMemoryRateLimitStore {
+    /// Creates an empty store.
+    ///
+    /// * `capacity` - Maximum number of tracked buckets.
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        Self {
+            buckets: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    /// Refills the buckets of keys at a timestamp and takes a token from each of them if all have one available.
+    ///
+    /// * `buckets` - Keys of the buckets with their limits, a missing bucket starts full.
+    /// * `now` - Timestamp of the call.
+    fn acquire_at(
+        &self,
+        buckets: &[(String, TokenBucketLimit)],
+        now: Instant,
+    ) -> Result<Acquisition, Box<dyn Error + Send + Sync>> {
+        let mut tracked_buckets = self
+            .buckets
+            .lock()
+            .map_err(|_| "Rate limit buckets are poisoned.")?;
+        let mut limited: Option<(usize, Duration)> = None;
+        for (bucket_index, (key, limit)) in buckets.iter().enumerate() {
+            let bucket = tracked_buckets.get_or_insert_mut(key.to_string(), || TokenBucket {
+                tokens: f64::from(limit.capacity),
+                last_refill: now,
+                limit: *limit,
+            });
+            bucket.limit = *limit;
+            bucket.refill(now);
+            if bucket.tokens >= 1.0 {
+                continue;
+            }
+            let retry_after = match limit.refill_per_minute {
+                0 => Duration::MAX,
+                _ => Duration::from_secs_f64((1.0 - bucket.tokens) / limit.refill_per_second()),
+            };
+            if limited.is_none_or(|(_, longest_retry_after)| retry_after > longest_retry_after) {
+                limited = Some((bucket_index, retry_after));
+            }
+        }
+        if let Some((bucket_index, retry_after)) = limited {
+            return Ok(Acquisition::Limited {
+                bucket_index,
+                retry_after,
+            });
+        }
+        for (key, _) in buckets {
+            if let Some(bucket) = tracked_buckets.get_mut(key) {
+                bucket.tokens -= 1.0;
+            }
+        }
+        Ok(Acquisition::Granted)
+    }
+}
+
+#[async_trait::async_trait]
+impl RateLimitStore for InMemoryRateLimitStore {
+    async fn acquire(
+        &self,
+        buckets: &[(String, TokenBucketLimit)],
+    ) -> Result<Acquisition, Box<dyn Error + Send + Sync>> {
+        self.acquire_at(buckets, Instant::now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMIT: TokenBucketLimit = TokenBucketLimit {
+        capacity: 2,
+        refill_per_minute: 60,
+    };
+
+    fn bucket(key: &str, limit: TokenBucketLimit) -> Vec<(String, TokenBucketLimit)> {
+        vec![(key.to_string(), limit)]
+    }
+
+    #[test]
+    fn grants_burst_up_to_capacity() {
+        let store = InMemoryRateLimitStore::default();
+        let now = Instant::now();
+        assert_eq!(
+            store.acquire_at(&bucket("key", LIMIT), now).unwrap(),
+            Acquisition::Granted
+        );
+        assert_eq!(
+            store.acquire_at(&bucket("key", LIMIT), now).unwrap(),
+            Acquisition::Granted
+        );
+        assert_eq!(
+            store.acquire_at(&bucket("key", LIMIT), now).unwrap(),
+            Acquisition::Limited {
+                bucket_index: 0,
+                retry_after: Duration::from_secs(1)
+            }
+        );
+    }
+
+    #[test]
+    fn retry_after_accounts_for_partial_refill() {
+        let store = InMemoryRateLimitStore::default();
+        let now = Instant::now();
+        store.acquire_at(&bucket("key", LIMIT), now).unwrap();
+        store.acquire_at(&bucket("key", LIMIT), now).unwrap();
+        let later = now + Duration::from_millis(250);
+        assert_eq!(
+            store.acquire_at(&bucket("key", LIMIT), later).unwrap(),
+            Acquisition::Limited {
+                bucket_index: 0,
+                retry_after: Duration::from_millis(750)
+            }
+        );
+    }
+
+    #[test]
+    fn refills_tokens_over_time_up_to_capacity() {
+        let store = InMemoryRateLimitStore::default();
+        let now = Instant::now();
+        store.acquire_at(&bucket("key", LIMIT), now).unwrap();
+        store.acquire_at(&bucket("key", LIMIT), now).unwrap();
+        let later = now + Duration::from_secs(1);
+        assert_eq!(
+            store.acquire_at(&bucket("key", LIMIT), later).unwrap(),
+            Acquisition::Granted
+        );
+        let much_later = later + Duration::from_secs(60);
+        for _ in 0..LIMIT.capacity {
+            assert_eq!(
+                store.acquire_at(&bucket("key", LIMIT), much_later).unwrap(),
+                Acquisition::Granted
+            );
+        }
+        assert!(matches!(
+            store.acquire_at(&bucket("key", LIMIT), much_later).unwrap(),
+            Acquisition::Limited { .. }
+        ));
+    }
+
+    #[test]
+    fn never_refills_without_refill_rate() {
+        let store = InMemoryRateLimitStore::default();
+        let limit = TokenBucketLimit {
+            capacity: 1,
+            refill_per_minute: 0,
+        };
+        let now = Instant::now();
+        store.acquire_at(&bucket("key", limit), now).unwrap();
+        assert_eq!(
+            store
+                .acquire_at(&bucket("key", limit), now + Duration::from_secs(3600))
+                .unwrap(),
+            Acquisition::Limited {
+                bucket_index: 0,
+                retry_after: Duration::MAX
+            }
+        );
+    }
+
+    #[test]
+    fn limits_keys_separately() {
+        let store = InMemoryRateLimitStore::default();
+        let limit = TokenBucketLimit {
+            capacity: 1,
+            refill_per_minute: 1,
+        };
+        let now = Instant::now();
+        store.acquire_at(&bucket("first", limit), now).unwrap();
+        assert_eq!(
+            store.acquire_at(&bucket("second", limit), now).unwrap(),
+            Acquisition::Granted
+        );
+    }
+
+    #[test]
+    fn evicts_least_recently_used_bucket_if_full() {
+        let store = InMemoryRateLimitStore::new(NonZeroUsize::new(2).unwrap());
+        let limit = TokenBucketLimit {
+            capacity: 1,
+            refill_per_minute: 1,
+        };
+        let now = Instant::now();
+        store.acquire_at(&bucket("first", limit), now).unwrap();
+        store.acquire_at(&bucket("second", limit), now).unwrap();
+        store.acquire_at(&bucket("third", limit), now).unwrap();
+        assert_eq!(store.buckets.lock().unwrap().len(), 2);
+        assert_eq!(
+            store.acquire_at(&bucket("first", limit), now).unwrap(),
+            Acquisition::Granted
+        );
+        assert!(matches!(
+            store.acquire_at(&bucket("third", limit), now).unwrap(),
+            Acquisition::Limited { .. }
+        ));
+    }
+
+    #[test]
+    fn takes_no_token_if_any_bucket_is_empty() {
+        let store = InMemoryRateLimitStore::default();
+        let limit = TokenBucketLimit {
+            capacity: 1,
+            refill_per_minute: 1,
+        };
+        let now = Instant::now();
+        store.acquire_at(&bucket("ip", limit), now).unwrap();
+        let buckets = vec![("user".to_string(), limit), ("ip".to_string(), limit)];
+        assert!(matches!(
+            store.acquire_at(&buckets, now).unwrap(),
+            Acquisition::Limited {
+                bucket_index: 1,
+                ..
+            }
+        ));
+        assert_eq!(
+            store.acquire_at(&bucket("user", limit), now).unwrap(),
+            Acquisition::Granted
+        );
+    }
+
+    #[test]
+    fn reports_empty_bucket_with_longest_wait() {
+        let store = InMemoryRateLimitStore::default();
+        let slow_limit = TokenBucketLimit {
+            capacity: 1,
+            refill_per_minute: 1,
+        };
+        let now = Instant::now();
+        store.acquire_at(&bucket("fast", LIMIT), now).unwrap();
+        store.acquire_at(&bucket("fast", LIMIT), now).unwrap();
+        store.acquire_at(&bucket("slow", slow_limit), now).unwrap();
+        let buckets = vec![
+            ("fast".to_string(), LIMIT),
+            ("slow".to_string(), slow_limit),
+        ];
+        assert_eq!(
+            store.acquire_at(&buckets, now).unwrap(),
+            Acquisition::Limited {
+                bucket_index: 1,
+                retry_after: Duration::from_secs(60)
+            }
+        );
+    }
+}
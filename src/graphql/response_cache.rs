@@ -0,0 +1,247 @@
+use std::{
+    future::Future,
+    hash::Hash,
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use async_graphql::Result;
+use bson::Uuid;
+use lru::LruCache;
+use once_cell::sync::Lazy;
+
+use super::{
+    loader::{
+        rating_summary_loader::RatingSummary, review_connection_loader::ReviewConnectionKey,
+        ReviewParent,
+    },
+    model::{connection::review_connection::ReviewConnection, review::Review},
+};
+
+/// Seconds cached reads are valid, equal to the `max-age` cache-control hint of the cached fields.
+pub const CACHE_MAX_AGE_SECONDS: u64 = 60;
+
+/// Maximum number of cached entries per kind of read.
+const CACHE_CAPACITY: usize = 10_000;
+
+/// Cache of public review reads, shared by all requests of this replica.
+pub static RESPONSE_CACHE: Lazy<ResponseCache> = Lazy::new(ResponseCache::in_memory);
+
+/// Store of cached values of one kind of read.
+///
+/// The in-memory store is separate for each replica, so invalidations only reach the replica which handled
+/// the mutation or event, other replicas serve stale reads until they expire after `CACHE_MAX_AGE_SECONDS`.
+/// A shared store, e.g. backed by Redis, can implement this trait to invalidate reads across replicas.
+#[async_trait::async_trait]
+pub trait ResponseCacheStore<K: Sync, V: Send>: Send + Sync {
+    /// Returns the cached value of a key if it has not expired.
+    ///
+    /// * `key` - Key of the value.
+    async fn get(&self, key: &K) -> Option<V>;
+
+    /// Returns the number of invalidations so far, so that values loaded before an invalidation are not cached.
+    async fn generation(&self) -> u64;
+
+    /// Caches the value of a key unless the store has been invalidated since a generation.
+    ///
+    /// * `key` - Key of the value.
+    /// * `value` - Value to cache.
+    /// * `generation` - Generation of the store before the value was loaded.
+    async fn set(&self, key: K, value: V, generation: u64);
+
+    /// Removes all entries of keys matching a predicate.
+    ///
+    /// * `predicate` - Returns true for keys to remove.
+    async fn invalidate_if(&self, predicate: &(dyn for<'k> Fn(&'k K) -> bool + Send + Sync));
+}
+
+/// Cache of review connections, rating summaries and reviews.
+///
+/// Review connections are keyed by their arguments and the visibility scope of the user.
+/// Reviews are cached regardless of the user, their visibility is checked on every read.
+/// Entries expire after `CACHE_MAX_AGE_SECONDS`, review mutations and events invalidate them earlier
+/// in the stores, which are local to this replica unless shared stores are used.
+pub struct ResponseCache {
+    review_connections: Arc<dyn ResponseCacheStore<ReviewConnectionKey, ReviewConnection>>,
+    rating_summaries: Arc<dyn ResponseCacheStore<ReviewParent, RatingSummary>>,
+    reviews: Arc<dyn ResponseCacheStore<Uuid, Option<Review>>>,
+}
+
+impl ResponseCache {
+    /// Creates a cache from stores of each kind of read.
+    ///
+    /// * `review_connections` - Store of review connections.
+    /// * `rating_summaries` - Store of rating summaries.
+    /// * `reviews` - Store of reviews.
+    pub fn new(
+        review_connections: Arc<dyn ResponseCacheStore<ReviewConnectionKey, ReviewConnection>>,
+        rating_summaries: Arc<dyn ResponseCacheStore<ReviewParent, RatingSummary>>,
+        reviews: Arc<dyn ResponseCacheStore<Uuid, Option<Review>>>,
+    ) -> Self {
+        Self {
+            review_connections,
+            rating_summaries,
+            reviews,
+        }
+    }
+
+    /// Creates an empty cache held in the memory of this replica.
+    fn in_memory() -> Self {
+        Self::new(
+            Arc::new(ExpiringLruCache::new()),
+            Arc::new(ExpiringLruCache::new()),
+            Arc::new(ExpiringLruCache::new()),
+        )
+    }
+
+    /// Returns the cached review connection of a key or loads and caches it.
+    ///
+    /// * `key` - Parent and page of the review connection.
+    /// * `load` - Loads the review connection on a cache miss.
+    pub async fn review_connection(
+        &self,
+        key: &ReviewConnectionKey,
+        load: impl Future<Output = Result<ReviewConnection>>,
+    ) -> Result<ReviewConnection> {
+        get_or_load(self.review_connections.as_ref(), key, load).await
+    }
+
+    /// Returns the cached rating summary of a parent or loads and caches it.
+    ///
+    /// * `parent` - Entity the rating summary is calculated for.
+    /// * `load` - Loads the rating summary on a cache miss.
+    pub async fn rating_summary(
+        &self,
+        parent: &ReviewParent,
+        load: impl Future<Output = Result<RatingSummary>>,
+    ) -> Result<RatingSummary> {
+        get_or_load(self.rating_summaries.as_ref(), parent, load).await
+    }
+
+    /// Returns the cached review of UUID or loads and caches it.
+    ///
+    /// * `id` - UUID of the review.
+    /// * `load` - Loads the review on a cache miss.
+    pub async fn review(
+        &self,
+        id: &Uuid,
+        load: impl Future<Output = Result<Option<Review>>>,
+    ) -> Result<Option<Review>> {
+        get_or_load(self.reviews.as_ref(), id, load).await
+    }
+
+    /// Invalidates all cached reads containing a review, i.e. the review and the reads of its user,
+    /// product variant and product.
+    ///
+    /// * `review` - Created, updated or deleted review.
+    pub async fn invalidate_review(&self, review: &Review) {
+        let parents = [
+            ReviewParent::User(review.user._id),
+            ReviewParent::ProductVariant(review.product_variant._id),
+            ReviewParent::Product(review.product_variant.product_id),
+        ];
+        let review_id = review._id;
+        self.review_connections
+            .invalidate_if(&|key| parents.contains(&key.parent))
+            .await;
+        self.rating_summaries
+            .invalidate_if(&|parent| parents.contains(parent))
+            .await;
+        self.reviews.invalidate_if(&|id| *id == review_id).await;
+    }
+
+    /// Invalidates all cached reads, e.g. after events changing many reviews.
+    pub async fn invalidate_all(&self) {
+        self.review_connections.invalidate_if(&|_| true).await;
+        self.rating_summaries.invalidate_if(&|_| true).await;
+        self.reviews.invalidate_if(&|_| true).await;
+    }
+}
+
+/// Returns the cached value of a key or loads and caches it.
+///
+/// Failed loads and loads overlapping an invalidation are not cached.
+///
+/// * `store` - Store of the kind of read.
+/// * `key` - Key of the value.
+/// * `load` - Loads the value on a cache miss.
+async fn get_or_load<K: Clone + Sync, V: Clone + Send>(
+    store: &dyn ResponseCacheStore<K, V>,
+    key: &K,
+    load: impl Future<Output = Result<V>>,
+) -> Result<V> {
+    if let Some(value) = store.get(key).await {
+        return Ok(value);
+    }
+    let generation = store.generation().await;
+    let value = load.await?;
+    store.set(key.clone(), value.clone(), generation).await;
+    Ok(value)
+}
+
+/// LRU cache of values expiring after `CACHE_MAX_AGE_SECONDS`, held in the memory of this replica.
+struct ExpiringLruCache<K: Hash + Eq, V> {
+    entries: Mutex<LruCache<K, (Instant, V)>>,
+    /// Number of invalidations, so that values loaded before an invalidation are not cached.
+    generation: AtomicU64,
+}
+
+impl<K: Hash + Eq, V> ExpiringLruCache<K, V> {
+    /// Creates an empty cache with a capacity of `CACHE_CAPACITY` entries.
+    fn new() -> Self {
+        let capacity = NonZeroUsize::new(CACHE_CAPACITY).unwrap();
+        Self {
+            entries: Mutex::new(LruCache::new(capacity)),
+            generation: AtomicU64::new(0),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<K, V> ResponseCacheStore<K, V> for ExpiringLruCache<K, V>
+where
+    K: Hash + Eq + Clone + Send + Sync,
+    V: Clone + Send + Sync,
+{
+    async fn get(&self, key: &K) -> Option<V> {
+        let mut entries = self.entries.lock().ok()?;
+        match entries.get(key) {
+            Some((cached_at, value))
+                if cached_at.elapsed() < Duration::from_secs(CACHE_MAX_AGE_SECONDS) =>
+            {
+                Some(value.clone())
+            }
+            _ => None,
+        }
+    }
+
+    async fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    async fn set(&self, key: K, value: V, generation: u64) {
+        if let Ok(mut entries) = self.entries.lock()
+            && self.generation.load(Ordering::Acquire) == generation
+        {
+            entries.put(key, (Instant::now(), value));
+        }
+    }
+
+    async fn invalidate_if(&self, predicate: &(dyn for<'k> Fn(&'k K) -> bool + Send + Sync)) {
+        if let Ok(mut entries) = self.entries.lock() {
+            self.generation.fetch_add(1, Ordering::AcqRel);
+            let keys: Vec<K> = entries
+                .iter()
+                .filter(|(key, _)| predicate(key))
+                .map(|(key, _)| key.clone())
+                .collect();
+            for key in keys {
+                entries.pop(&key);
+            }
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use async_graphql::{Error, Result};
+use bson::{doc, DateTime, Uuid};
+use futures::TryStreamExt;
+use mongodb::{options::FindOptions, Collection, Database};
+use serde::Serialize;
+
+use super::model::{
+    review::Review,
+    review_record::ReviewRecord,
+    review_vote::{find_votes_of_user, ReviewVote},
+};
+
+/// Document containing all data the service stores about a user.
+///
+/// Reports and revisions of reviews are not stored by the service and are therefore not part of the export.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UserDataExport {
+    /// UUID of the exported user.
+    user_id: String,
+    /// Timestamp of the export in RFC 3339 format.
+    exported_at: String,
+    /// Reviews written by the user.
+    reviews: Vec<ReviewRecord>,
+    /// Votes of the user on reviews of other users.
+    review_votes: Vec<ReviewVoteRecord>,
+}
+
+/// Vote of the user on a review.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ReviewVoteRecord {
+    /// UUID of the review voted on.
+    review_id: String,
+    /// Flag if the user voted the review helpful, otherwise unhelpful.
+    is_helpful: bool,
+    /// Timestamp when the user last voted on the review in RFC 3339 format.
+    voted_at: Option<String>,
+}
+
+impl From<&ReviewVote> for ReviewVoteRecord {
+    fn from(value: &ReviewVote) -> Self {
+        Self {
+            review_id: value._id.review_id.to_string(),
+            is_helpful: value.is_helpful,
+            voted_at: value.voted_at.try_to_rfc3339_string().ok(),
+        }
+    }
+}
+
+/// Exports the data of a user as JSON document.
+///
+/// * `db_client` - MongoDB database client.
+/// * `user_id` - UUID of the user to export the data of.
+pub async fn export_user_data(db_client: &Database, user_id: Uuid) -> Result<String> {
+    let collection: Collection<Review> = db_client.collection::<Review>("reviews");
+    let find_options = FindOptions::builder().sort(doc! {"created_at": 1}).build();
+    let message = format!(
+        "Exporting data of user of UUID: `{}` failed in MongoDB.",
+        user_id
+    );
+    let reviews: Vec<Review> = match collection
+        .find(doc! {"user._id": user_id }, find_options)
+        .await
+    {
+        Ok(cursor) => cursor
+            .try_collect()
+            .await
+            .map_err(|_| Error::new(message.clone()))?,
+        Err(_) => return Err(Error::new(message)),
+    };
+    let vote_collection: Collection<ReviewVote> =
+        db_client.collection::<ReviewVote>("review_votes");
+    let review_votes = find_votes_of_user(&vote_collection, user_id)
+        .await
+        .map_err(|_| Error::new(message.clone()))?;
+    let user_data_export = UserDataExport {
+        user_id: user_id.to_string(),
+        exported_at: DateTime::now().try_to_rfc3339_string()?,
+        reviews: reviews.iter().map(ReviewRecord::from).collect(),
+        review_votes: review_votes.iter().map(ReviewVoteRecord::from).collect(),
+    };
+    Ok(serde_json::to_string_pretty(&user_data_export)?)
+}
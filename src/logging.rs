@@ -0,0 +1,136 @@
+use std::{env, future::Future, io::Write, str::FromStr};
+
+use axum::http::HeaderMap;
+use bson::{DateTime, Uuid};
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+use opentelemetry::trace::TraceContextExt;
+use serde_json::{Map, Value};
+
+/// Log level used if `$LOG_LEVEL` is not set or invalid.
+const DEFAULT_LOG_LEVEL: LevelFilter = LevelFilter::Info;
+
+/// Header which is used to correlate a request across services.
+const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+tokio::task_local! {
+    /// Correlation fields of the request or event processed by the current task.
+    static LOG_CONTEXT: LogContext;
+}
+
+/// Correlation fields written with every log record emitted while processing a request or event.
+#[derive(Clone, Debug, Default)]
+pub struct LogContext {
+    /// Id of the HTTP request, taken from the `X-Request-Id` header or generated.
+    pub request_id: Option<String>,
+    /// UUID of the user from the `Authorized-User` header.
+    pub user_id: Option<Uuid>,
+    /// Name of the executed GraphQL operation.
+    pub operation_name: Option<String>,
+    /// Id of the received Dapr event.
+    pub event_id: Option<String>,
+    /// Topic of the received Dapr event.
+    pub topic: Option<String>,
+}
+
+impl LogContext {
+    /// Creates a log context with the request id of the `X-Request-Id` header or a newly generated one.
+    ///
+    /// * `headers` - Header map of the request.
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let request_id = headers
+            .get(REQUEST_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(|request_id| request_id.to_string())
+            .unwrap_or_else(|| Uuid::new().to_string());
+        Self {
+            request_id: Some(request_id),
+            ..Default::default()
+        }
+    }
+
+    /// Executes a future with this log context as correlation fields of its log records.
+    ///
+    /// * `future` - Future to execute.
+    pub async fn scope<F: Future>(self, future: F) -> F::Output {
+        LOG_CONTEXT.scope(self, future).await
+    }
+
+    /// Writes all set correlation fields into a JSON object.
+    ///
+    /// * `fields` - JSON object of a log record.
+    fn write_fields(&self, fields: &mut Map<String, Value>) {
+        let mut insert = |key: &str, value: Option<String>| {
+            if let Some(definitely_value) = value {
+                fields.insert(key.to_string(), Value::String(definitely_value));
+            }
+        };
+        insert("request_id", self.request_id.clone());
+        insert("user_id", self.user_id.map(|user_id| user_id.to_string()));
+        insert("operation_name", self.operation_name.clone());
+        insert("event_id", self.event_id.clone());
+        insert("topic", self.topic.clone());
+    }
+}
+
+/// Logger writing one JSON object per log record to stdout.
+struct JsonLogger;
+
+static LOGGER: JsonLogger = JsonLogger;
+
+impl Log for JsonLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let mut fields = Map::new();
+        if let Ok(timestamp) = DateTime::now().try_to_rfc3339_string() {
+            fields.insert("timestamp".to_string(), Value::String(timestamp));
+        }
+        fields.insert(
+            "level".to_string(),
+            Value::String(record.level().to_string()),
+        );
+        fields.insert(
+            "target".to_string(),
+            Value::String(record.target().to_string()),
+        );
+        fields.insert(
+            "message".to_string(),
+            Value::String(record.args().to_string()),
+        );
+        let _ = LOG_CONTEXT.try_with(|log_context| log_context.write_fields(&mut fields));
+        let otel_context = opentelemetry::Context::current();
+        let span_context = otel_context.span().span_context().clone();
+        if span_context.is_valid() {
+            fields.insert(
+                "trace_id".to_string(),
+                Value::String(span_context.trace_id().to_string()),
+            );
+            fields.insert(
+                "span_id".to_string(),
+                Value::String(span_context.span_id().to_string()),
+            );
+        }
+        let mut stdout = std::io::stdout().lock();
+        let _ = writeln!(stdout, "{}", Value::Object(fields));
+    }
+
+    fn flush(&self) {
+        let _ = std::io::stdout().flush();
+    }
+}
+
+/// Initializes the JSON logger with the level of `$LOG_LEVEL`, e.g. `debug`, defaulting to `info`.
+pub fn init_logger() -> Result<(), SetLoggerError> {
+    let level = env::var("LOG_LEVEL")
+        .ok()
+        .and_then(|level| LevelFilter::from_str(&level).ok())
+        .unwrap_or(DEFAULT_LOG_LEVEL);
+    log::set_logger(&LOGGER)?;
+    log::set_max_level(level);
+    Ok(())
+}
@@ -1,42 +1,74 @@
-use std::{env, fs::File, io::Write};
+use std::{env, fs::File, io::Write, net::SocketAddr, path::PathBuf, sync::Arc};
 
 use async_graphql::{
     extensions::Logger, http::GraphiQLSource, EmptySubscription, SDLExportOptions, Schema,
 };
 use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
 
-use authorization::AuthorizedUserHeader;
+use authorization::{jwt::JWT_VERIFIER, permission::ROLE_PERMISSIONS, AuthorizedUserHeader};
 use axum::{
-    extract::State,
+    extract::{ConnectInfo, State},
     http::{header::HeaderMap, StatusCode},
     response::{self, IntoResponse},
-    routing::{get, post},
+    routing::get,
     Router,
 };
-use clap::{arg, command, Parser};
-use event::http_event_service::{
-    list_topic_subscriptions, on_product_variant_creation_event, on_topic_event,
-    HttpEventServiceState,
+use clap::{Parser, Subcommand};
+use cli::{
+    resync::{resync, DumpFormat},
+    review_export::{export_reviews, ReviewExportFilter},
+    review_import::import_reviews,
+    review_file_format::ReviewFileFormat,
+};
+use event::{
+    http_event_service::{list_topic_subscriptions, HttpEventServiceState},
+    processed_event::create_processed_event_index,
+    topic_handlers::TOPIC_REGISTRY,
 };
-use graphql::model::{product::Product, product_variant::ProductVariant, user::User};
 
-use log::{info, Level};
-use mongodb::{options::ClientOptions, Client, Database};
+use log::{info, warn};
+use logging::{init_logger, LogContext};
+use mongodb::{bson::Document, options::ClientOptions, Client, Database};
+use telemetry::{
+    graphql_tracing::GraphQLTracing,
+    metrics::METRICS,
+    mongodb_monitoring::MongoDbMonitor,
+    propagation::{extract_context, HeaderExtractor},
+};
 
-use crate::graphql::{mutation::Mutation, query::Query};
+use crate::graphql::{
+    loader::register_loaders,
+    model::{
+        review::{create_review_parent_indexes, migrate_legacy_ratings},
+        review_vote::create_review_vote_index,
+        reviewer_statistics::REVIEWER_RANK_THRESHOLDS,
+    },
+    mutation::Mutation,
+    persisted_queries::PersistedQueries,
+    query::Query,
+    query_limits::{MAX_QUERY_COMPLEXITY, MAX_QUERY_DEPTH},
+    rate_limit::{
+        store::InMemoryRateLimitStore, ClientIp, RateLimit, RateLimitConfig, TRUSTED_PROXIES,
+    },
+};
 
 use once_cell::sync::Lazy;
 use axum_otel_metrics::HttpMetricsLayerBuilder;
 use axum_otel_metrics::HttpMetricsLayer;
 
-use opentelemetry::global;
+use opentelemetry::{global, trace::FutureExt};
 use opentelemetry_sdk::metrics::{PeriodicReader, SdkMeterProvider, Temporality};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::SdkTracerProvider;
 use opentelemetry_sdk::Resource;
 use opentelemetry_otlp::WithExportConfig;
 
 mod authorization;
+mod cli;
 mod event;
 mod graphql;
+mod logging;
+mod telemetry;
 
 /// Builds the GraphiQL frontend.
 async fn graphiql() -> impl IntoResponse {
@@ -56,51 +88,89 @@ async fn db_connection() -> Client {
     // Manually set an option.
     client_options.app_name = Some("Review".to_string());
 
+    // Create a span and record the latency of every MongoDB command.
+    client_options.command_event_handler = Some(Arc::new(MongoDbMonitor::default()));
+
     // Get a handle to the deployment.
     Client::with_options(client_options).unwrap()
 }
 
 /// Returns Router that establishes connection to Dapr.
 ///
-/// Adds endpoints to define pub/sub interaction with Dapr.
+/// Adds endpoints to define pub/sub interaction with Dapr, the event routes are generated from the topic registry.
 ///
 /// * `db_client` - MongoDB database client.
 async fn build_dapr_router(db_client: Database) -> Router {
-    let product_collection: mongodb::Collection<Product> =
-        db_client.collection::<Product>("products");
-    let product_variant_collection: mongodb::Collection<ProductVariant> =
-        db_client.collection::<ProductVariant>("product_variants");
-    let user_collection: mongodb::Collection<User> = db_client.collection::<User>("users");
+    let state = HttpEventServiceState::new(&db_client);
+    if let Err(error) = create_processed_event_index(&state.processed_event_collection).await {
+        warn!("Creating index of processed events failed: {}", error);
+    }
+    if let Err(error) = create_review_vote_index(&state.review_vote_collection).await {
+        warn!("Creating index of review votes failed: {}", error);
+    }
+    if let Err(error) = create_review_parent_indexes(&state.review_collection).await {
+        warn!("Creating indexes of reviews failed: {}", error);
+    }
 
     // Define routes.
-    let app = Router::new()
+    Router::new()
         .route("/dapr/subscribe", get(list_topic_subscriptions))
-        .route("/on-topic-event", post(on_topic_event))
-        .route(
-            "/on-product-variant-creation-event",
-            post(on_product_variant_creation_event),
-        )
-        .with_state(HttpEventServiceState {
-            product_collection,
-            product_variant_collection,
-            user_collection,
-        });
-    app
+        .merge(TOPIC_REGISTRY.router())
+        .with_state(state)
 }
 
-/// Command line argument to toggle schema generation instead of service execution.
+/// Command line arguments to toggle schema generation or maintenance commands instead of service execution.
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
     /// Generates GraphQL schema in `./schemas/review.graphql`.
     #[arg(long)]
     generate_schema: bool,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// Maintenance commands which run instead of the service.
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Imports users, products and product variants from a dump file and reconciles them with the mirrored entities.
+    Resync {
+        /// Path of the dump file.
+        file: PathBuf,
+        /// Format of the dump file, inferred from the file extension if omitted.
+        #[arg(long, value_enum)]
+        format: Option<DumpFormat>,
+    },
+    /// Exports reviews matching the filters to an NDJSON or CSV file.
+    ExportReviews {
+        /// Path of the export file.
+        file: PathBuf,
+        /// Format of the export file, inferred from the file extension if omitted.
+        #[arg(long, value_enum)]
+        format: Option<ReviewFileFormat>,
+        #[command(flatten)]
+        filter: ReviewExportFilter,
+    },
+    /// Imports reviews from an NDJSON or CSV file, rejecting invalid and duplicate reviews.
+    ImportReviews {
+        /// Path of the import file.
+        file: PathBuf,
+        /// Format of the import file, inferred from the file extension if omitted.
+        #[arg(long, value_enum)]
+        format: Option<ReviewFileFormat>,
+        /// Only validates the reviews without inserting them.
+        #[arg(long)]
+        dry_run: bool,
+        /// Path of the report of rejected records, by default `<FILE>.errors.ndjson`.
+        #[arg(long)]
+        error_report: Option<PathBuf>,
+    },
 }
 
-/// Activates logger and parses argument for optional schema generation. Otherwise starts gRPC and GraphQL server.
+/// Activates logger and parses argument for optional schema generation or maintenance commands. Otherwise starts gRPC and GraphQL server.
 #[tokio::main]
 async fn main() -> std::io::Result<()> {
-    simple_logger::init_with_level(Level::Warn).unwrap();
+    init_logger().unwrap();
 
     let args = Args::parse();
     if args.generate_schema {
@@ -110,30 +180,71 @@ async fn main() -> std::io::Result<()> {
         let schema_sdl = schema.sdl_with_options(sdl_export_options);
         file.write_all(schema_sdl.as_bytes())?;
         info!("GraphQL schema: ./schemas/review.graphql was successfully generated!");
+    } else if let Some(command) = args.command {
+        run_command(command)
+            .await
+            .map_err(|error| std::io::Error::other(error.to_string()))?;
     } else {
         start_service().await;
     }
     Ok(())
 }
 
+/// Runs a maintenance command against the review database.
+///
+/// * `command` - Maintenance command to run.
+async fn run_command(command: Command) -> Result<(), Box<dyn std::error::Error>> {
+    let db_client = db_connection().await.database("review-database");
+    match command {
+        Command::Resync { file, format } => resync(&db_client, &file, format).await,
+        Command::ExportReviews {
+            file,
+            format,
+            filter,
+        } => export_reviews(&db_client, &file, format, &filter).await,
+        Command::ImportReviews {
+            file,
+            format,
+            dry_run,
+            error_report,
+        } => import_reviews(&db_client, &file, format, dry_run, error_report).await,
+    }
+}
+
 /// Describes the handler for GraphQL requests.
 ///
-/// Parses the `Authorized-User` header and writes it in the context data of the specfic request.
-/// Then executes the GraphQL schema with the request.
+/// Parses the `Authorized-User` header, or verifies a bearer token if configured, and writes it in the context data of the specfic request.
+/// Then executes the GraphQL schema with the request, continuing a trace propagated by the `traceparent` header.
+/// Log records emitted during execution carry the request id, user id and operation name.
+/// Cache-control hints of responses to authorized users are private, since they may contain hidden reviews.
 ///
 /// * `schema` - GraphQL schema used by handler.
+/// * `remote_addr` - Address of the connection, used as client IP address if not forwarded by a trusted proxy.
 /// * `headers` - Header map containing headers of request.
 /// * `request` - GraphQL request.
 async fn graphql_handler(
     State(schema): State<Schema<Query, Mutation, EmptySubscription>>,
+    ConnectInfo(remote_addr): ConnectInfo<SocketAddr>,
     headers: HeaderMap,
     req: GraphQLRequest,
 ) -> GraphQLResponse {
     let mut req = req.into_inner();
-    if let Ok(authenticate_user_header) = AuthorizedUserHeader::try_from(&headers) {
+    let mut log_context = LogContext::from_headers(&headers);
+    log_context.operation_name = req.operation_name.clone();
+    req = req.data(ClientIp::from_request(&headers, remote_addr, &TRUSTED_PROXIES));
+    if let Ok(authenticate_user_header) = AuthorizedUserHeader::from_headers(&headers) {
+        log_context.user_id = Some(authenticate_user_header.id);
         req = req.data(authenticate_user_header);
     }
-    schema.execute(req).await.into()
+    let is_authorized = log_context.user_id.is_some();
+    let parent_context = extract_context(&HeaderExtractor(&headers));
+    let mut response = log_context
+        .scope(schema.execute(req).with_context(parent_context))
+        .await;
+    if is_authorized {
+        response.cache_control.public = false;
+    }
+    response.into()
 }
 
 static RESOURCE: Lazy<Resource> = Lazy::new(|| {
@@ -142,14 +253,17 @@ static RESOURCE: Lazy<Resource> = Lazy::new(|| {
         .build()
 });
 
-/// Initializes OpenTelemetry metrics exporter and sets the global meter provider.
-fn init_otlp() -> HttpMetricsLayer {
-    let otlp_url = match env::var_os("OTEL_EXPORTER_OTLP_ENDPOINT") {
+/// Returns the base URL of the OTLP collector.
+fn otlp_url() -> String {
+    match env::var_os("OTEL_EXPORTER_OTLP_ENDPOINT") {
         Some(uri) => uri.into_string().unwrap(),
         None => "http://localhost:4318".to_string(),
-    };
+    }
+}
 
-    let otlp_endpoint = format!("{}/v1/metrics", otlp_url.trim_end_matches('/'));
+/// Initializes OpenTelemetry metrics exporter, sets the global meter provider and registers the domain metrics on it.
+fn init_otlp() -> HttpMetricsLayer {
+    let otlp_endpoint = format!("{}/v1/metrics", otlp_url().trim_end_matches('/'));
 
     let exporter = opentelemetry_otlp::MetricExporter::builder()
         .with_http()
@@ -168,20 +282,66 @@ fn init_otlp() -> HttpMetricsLayer {
         .build();
 
     global::set_meter_provider(provider.clone());
+    Lazy::force(&METRICS);
 
     HttpMetricsLayerBuilder::new()
         .with_provider(provider.clone())
         .build()
 }
 
+/// Initializes OpenTelemetry trace exporter, sets the global tracer provider and the W3C trace context propagator.
+fn init_otlp_tracing() -> SdkTracerProvider {
+    let otlp_endpoint = format!("{}/v1/traces", otlp_url().trim_end_matches('/'));
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint(otlp_endpoint)
+        .build()
+        .unwrap();
+
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(RESOURCE.clone())
+        .build();
+
+    global::set_tracer_provider(provider.clone());
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    provider
+}
+
 /// Starts review service on port 8000.
 async fn start_service() {
+    Lazy::force(&ROLE_PERMISSIONS);
+    Lazy::force(&JWT_VERIFIER);
+    Lazy::force(&REVIEWER_RANK_THRESHOLDS);
+    Lazy::force(&TRUSTED_PROXIES);
+    let rate_limit_config =
+        RateLimitConfig::from_env().unwrap_or_else(|message| panic!("{}", message));
+    let persisted_queries =
+        PersistedQueries::from_env().unwrap_or_else(|message| panic!("{}", message));
+    let tracer_provider = init_otlp_tracing();
+    let metrics = init_otlp();
     let client = db_connection().await;
     let db_client: Database = client.database("review-database");
+    match migrate_legacy_ratings(&db_client.collection::<Document>("reviews")).await {
+        Ok(0) => {}
+        Ok(migrated_count) => info!("Migrated ratings of {} reviews.", migrated_count),
+        Err(error) => warn!("Migrating legacy ratings failed: {}", error),
+    }
 
-    let schema = Schema::build(Query, Mutation, EmptySubscription)
+    let schema_builder = Schema::build(Query, Mutation, EmptySubscription)
         .extension(Logger)
-        .data(db_client.clone())
+        .extension(GraphQLTracing)
+        .extension(persisted_queries)
+        .extension(RateLimit::new(
+            rate_limit_config,
+            Arc::new(InMemoryRateLimitStore::default()),
+        ))
+        .limit_depth(MAX_QUERY_DEPTH)
+        .limit_complexity(MAX_QUERY_COMPLEXITY)
+        .data(db_client.clone());
+    let schema = register_loaders(schema_builder, &db_client)
         .enable_federation()
         .finish();
 
@@ -190,7 +350,6 @@ async fn start_service() {
         .route("/health", get(StatusCode::OK))
         .with_state(schema);
     let dapr_router = build_dapr_router(db_client).await;
-    let metrics = init_otlp();
 
     let app = Router::new()
         .merge(graphiql)
@@ -200,7 +359,11 @@ async fn start_service() {
     info!("GraphiQL IDE: http://0.0.0.0:8080");
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:8080").await.unwrap();
-    axum::serve(listener, app)
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
         .await
         .unwrap();
+
+    if let Err(error) = tracer_provider.shutdown() {
+        warn!("Flushing remaining spans failed: {}", error);
+    }
 }
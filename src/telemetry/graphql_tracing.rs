@@ -0,0 +1,120 @@
+use std::sync::Arc;
+
+use async_graphql::{
+    extensions::{
+        Extension, ExtensionContext, ExtensionFactory, NextExecute, NextRequest, NextResolve,
+        ResolveInfo,
+    },
+    registry::MetaTypeName,
+    Response, ServerResult, Value,
+};
+use opentelemetry::{
+    global,
+    trace::{FutureExt, SpanKind, Status, TraceContextExt, Tracer},
+    Context, KeyValue,
+};
+
+use super::propagation::TRACER_NAME;
+
+/// GraphQL extension creating OpenTelemetry spans for requests, operations and resolvers.
+///
+/// The request span is a child of the context the request future is executed in,
+/// which allows continuing a trace propagated via HTTP headers.
+pub struct GraphQLTracing;
+
+impl ExtensionFactory for GraphQLTracing {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(GraphQLTracingExtension)
+    }
+}
+
+struct GraphQLTracingExtension;
+
+#[async_trait::async_trait]
+impl Extension for GraphQLTracingExtension {
+    /// Wraps the whole GraphQL request in a server span.
+    async fn request(&self, ctx: &ExtensionContext<'_>, next: NextRequest<'_>) -> Response {
+        let tracer = global::tracer(TRACER_NAME);
+        let span = tracer
+            .span_builder("graphql.request")
+            .with_kind(SpanKind::Server)
+            .start(&tracer);
+        next.run(ctx)
+            .with_context(Context::current_with_span(span))
+            .await
+    }
+
+    /// Creates a span for the executed operation, named after the operation.
+    async fn execute(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        operation_name: Option<&str>,
+        next: NextExecute<'_>,
+    ) -> Response {
+        let tracer = global::tracer(TRACER_NAME);
+        let name = operation_name.unwrap_or("anonymous").to_string();
+        let span = tracer
+            .span_builder(format!("graphql.execute {}", name))
+            .with_kind(SpanKind::Internal)
+            .with_attributes(vec![KeyValue::new("graphql.operation.name", name)])
+            .start(&tracer);
+        let span_context = Context::current_with_span(span);
+        let response = next
+            .run(ctx, operation_name)
+            .with_context(span_context.clone())
+            .await;
+        if response.is_err() {
+            let messages: Vec<String> = response
+                .errors
+                .iter()
+                .map(|error| error.message.clone())
+                .collect();
+            span_context
+                .span()
+                .set_status(Status::error(messages.join("; ")));
+        }
+        response
+    }
+
+    /// Creates a span for each resolver returning an object, list or connection.
+    ///
+    /// Spans are named after parent type and field, e.g. `Product.reviews`, to keep span names low-cardinality,
+    /// the path including list indices is recorded as `graphql.field.path` attribute.
+    /// Scalar and enum fields as well as introspection are skipped to avoid span explosion.
+    async fn resolve(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        info: ResolveInfo<'_>,
+        next: NextResolve<'_>,
+    ) -> ServerResult<Option<Value>> {
+        let concrete_type = MetaTypeName::concrete_typename(info.return_type);
+        let is_leaf = ctx
+            .schema_env
+            .registry
+            .types
+            .get(concrete_type)
+            .map(|meta_type| meta_type.is_leaf())
+            .unwrap_or(false);
+        if info.is_for_introspection || is_leaf {
+            return next.run(ctx, info).await;
+        }
+        let tracer = global::tracer(TRACER_NAME);
+        let span = tracer
+            .span_builder(format!("{}.{}", info.parent_type, info.name))
+            .with_kind(SpanKind::Internal)
+            .with_attributes(vec![
+                KeyValue::new("graphql.field.path", info.path_node.to_string()),
+                KeyValue::new("graphql.parent_type", info.parent_type.to_string()),
+                KeyValue::new("graphql.return_type", info.return_type.to_string()),
+            ])
+            .start(&tracer);
+        let span_context = Context::current_with_span(span);
+        let result = next.run(ctx, info).with_context(span_context.clone()).await;
+        if let Err(error) = &result {
+            span_context
+                .span()
+                .set_status(Status::error(error.message.clone()));
+        }
+        result
+    }
+}
@@ -0,0 +1,94 @@
+use once_cell::sync::Lazy;
+use opentelemetry::{
+    global,
+    metrics::{Counter, Histogram},
+    KeyValue,
+};
+
+use crate::graphql::model::review::Rating;
+
+/// Name of the meter used for all instruments of the review service.
+const METER_NAME: &str = "review";
+
+/// Domain metrics of the review service.
+///
+/// Instruments are created on first access from the global meter provider,
+/// which therefore has to be set beforehand in `init_otlp`.
+pub static METRICS: Lazy<ReviewMetrics> = Lazy::new(ReviewMetrics::new);
+
+/// OpenTelemetry instruments for reviews, authorization, events and MongoDB.
+pub struct ReviewMetrics {
+    /// Number of created reviews by rating.
+    pub reviews_created: Counter<u64>,
+    /// Number of updated reviews by rating after the update.
+    pub reviews_updated: Counter<u64>,
+    /// Number of deleted reviews by rating.
+    pub reviews_deleted: Counter<u64>,
+    /// Number of visibility changes by action and whether they were performed by a moderator.
+    pub moderation_actions: Counter<u64>,
+    /// Number of review creations rejected, since the user already reviewed the product variant.
+    pub duplicate_reviews_rejected: Counter<u64>,
+    /// Number of failed authorizations by reason.
+    pub authorization_failures: Counter<u64>,
+    /// Number of mutation calls rejected by rate limits by operation and scope.
+    pub rate_limited_calls: Counter<u64>,
+    /// Number of handled Dapr events by topic and outcome.
+    pub events_handled: Counter<u64>,
+    /// Duration of MongoDB commands in seconds by operation, collection and outcome.
+    pub mongodb_operation_duration: Histogram<f64>,
+}
+
+impl ReviewMetrics {
+    /// Creates all instruments on the global meter provider.
+    fn new() -> Self {
+        let meter = global::meter(METER_NAME);
+        Self {
+            reviews_created: meter
+                .u64_counter("reviews.created")
+                .with_description("Number of created reviews.")
+                .build(),
+            reviews_updated: meter
+                .u64_counter("reviews.updated")
+                .with_description("Number of updated reviews.")
+                .build(),
+            reviews_deleted: meter
+                .u64_counter("reviews.deleted")
+                .with_description("Number of deleted reviews.")
+                .build(),
+            moderation_actions: meter
+                .u64_counter("reviews.moderation_actions")
+                .with_description("Number of review visibility changes.")
+                .build(),
+            duplicate_reviews_rejected: meter
+                .u64_counter("reviews.duplicates_rejected")
+                .with_description(
+                    "Number of rejected reviews for already reviewed product variants.",
+                )
+                .build(),
+            authorization_failures: meter
+                .u64_counter("authorization.failures")
+                .with_description("Number of failed authorizations.")
+                .build(),
+            rate_limited_calls: meter
+                .u64_counter("rate_limit.rejected_calls")
+                .with_description("Number of mutation calls rejected by rate limits.")
+                .build(),
+            events_handled: meter
+                .u64_counter("events.handled")
+                .with_description("Number of handled Dapr events.")
+                .build(),
+            mongodb_operation_duration: meter
+                .f64_histogram("mongodb.operation.duration")
+                .with_description("Duration of MongoDB commands.")
+                .with_unit("s")
+                .build(),
+        }
+    }
+}
+
+/// Returns the attribute describing a review rating.
+///
+/// * `rating` - Rating of the review.
+pub fn rating_attribute(rating: Rating) -> KeyValue {
+    KeyValue::new("rating", rating.to_string())
+}
@@ -0,0 +1,4 @@
+pub mod graphql_tracing;
+pub mod metrics;
+pub mod mongodb_monitoring;
+pub mod propagation;
@@ -0,0 +1,156 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use mongodb::event::command::{
+    CommandEventHandler, CommandFailedEvent, CommandStartedEvent, CommandSucceededEvent,
+};
+use opentelemetry::{
+    global::{self, BoxedSpan},
+    trace::{Span, SpanKind, Status, Tracer},
+    Context, KeyValue,
+};
+
+use super::{metrics::METRICS, propagation::TRACER_NAME};
+
+/// Duration after which a started command without success or failure event is considered lost.
+///
+/// Longer than the server selection and connection timeouts of the driver, so that only commands
+/// the driver will never report on are evicted.
+const STALE_COMMAND_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// State of a MongoDB command which has been started but not completed yet.
+struct StartedCommand {
+    span: BoxedSpan,
+    collection_name: Option<String>,
+    started_at: Instant,
+}
+
+/// MongoDB command monitor creating a client span and recording the latency of every database command.
+///
+/// Spans are started on command start as children of the current context and ended when the
+/// driver reports success or failure of the command with the same request id.
+/// Commands without either event are evicted after `STALE_COMMAND_TIMEOUT`, so that they do not accumulate.
+#[derive(Default)]
+pub struct MongoDbMonitor {
+    started_commands: Mutex<HashMap<i32, StartedCommand>>,
+}
+
+impl CommandEventHandler for MongoDbMonitor {
+    fn handle_command_started_event(&self, event: CommandStartedEvent) {
+        let tracer = global::tracer(TRACER_NAME);
+        let mut attributes = vec![
+            KeyValue::new("db.system", "mongodb"),
+            KeyValue::new("db.namespace", event.db.clone()),
+            KeyValue::new("db.operation.name", event.command_name.clone()),
+        ];
+        let collection_name = event
+            .command
+            .get_str(&event.command_name)
+            .ok()
+            .map(|collection_name| collection_name.to_string());
+        if let Some(definitely_collection_name) = &collection_name {
+            attributes.push(KeyValue::new(
+                "db.collection.name",
+                definitely_collection_name.clone(),
+            ));
+        }
+        let span_name = match &collection_name {
+            Some(definitely_collection_name) => {
+                format!("{} {}", event.command_name, definitely_collection_name)
+            }
+            None => event.command_name.clone(),
+        };
+        let span = tracer
+            .span_builder(span_name)
+            .with_kind(SpanKind::Client)
+            .with_attributes(attributes)
+            .start_with_context(&tracer, &Context::current());
+        if let Ok(mut started_commands) = self.started_commands.lock() {
+            evict_stale_commands(&mut started_commands);
+            started_commands.insert(
+                event.request_id,
+                StartedCommand {
+                    span,
+                    collection_name,
+                    started_at: Instant::now(),
+                },
+            );
+        }
+    }
+
+    fn handle_command_succeeded_event(&self, event: CommandSucceededEvent) {
+        if let Some(mut started_command) = self.take_started_command(event.request_id) {
+            record_duration(&started_command, &event.command_name, event.duration, true);
+            started_command.span.set_status(Status::Ok);
+            started_command.span.end();
+        }
+    }
+
+    fn handle_command_failed_event(&self, event: CommandFailedEvent) {
+        if let Some(mut started_command) = self.take_started_command(event.request_id) {
+            record_duration(&started_command, &event.command_name, event.duration, false);
+            started_command
+                .span
+                .set_status(Status::error(event.failure.to_string()));
+            started_command.span.end();
+        }
+    }
+}
+
+impl MongoDbMonitor {
+    /// Removes and returns the state of the command of a request id.
+    ///
+    /// * `request_id` - Driver-generated identifier of the command.
+    fn take_started_command(&self, request_id: i32) -> Option<StartedCommand> {
+        self.started_commands
+            .lock()
+            .ok()
+            .and_then(|mut started_commands| started_commands.remove(&request_id))
+    }
+}
+
+/// Ends the spans of started commands older than `STALE_COMMAND_TIMEOUT` and removes them.
+///
+/// * `started_commands` - Started commands by request id.
+fn evict_stale_commands(started_commands: &mut HashMap<i32, StartedCommand>) {
+    started_commands.retain(|_, started_command| {
+        if started_command.started_at.elapsed() < STALE_COMMAND_TIMEOUT {
+            return true;
+        }
+        started_command
+            .span
+            .set_status(Status::error("Command did not report success or failure."));
+        started_command.span.end();
+        false
+    });
+}
+
+/// Records the duration of a completed MongoDB command.
+///
+/// * `started_command` - State of the command recorded on command start.
+/// * `command_name` - Name of the command, e.g. "find".
+/// * `duration` - Execution time of the command including the network round-trip.
+/// * `succeeded` - Whether the command completed successfully.
+fn record_duration(
+    started_command: &StartedCommand,
+    command_name: &str,
+    duration: Duration,
+    succeeded: bool,
+) {
+    let mut attributes = vec![
+        KeyValue::new("db.operation.name", command_name.to_string()),
+        KeyValue::new("outcome", if succeeded { "success" } else { "failure" }),
+    ];
+    if let Some(definitely_collection_name) = &started_command.collection_name {
+        attributes.push(KeyValue::new(
+            "db.collection.name",
+            definitely_collection_name.clone(),
+        ));
+    }
+    METRICS
+        .mongodb_operation_duration
+        .record(duration.as_secs_f64(), &attributes);
+}
@@ -0,0 +1,50 @@
+use axum::http::HeaderMap;
+use opentelemetry::{
+    global,
+    propagation::Extractor,
+    trace::{SpanKind, TraceContextExt, Tracer},
+    Context, KeyValue,
+};
+
+/// Name of the tracer used for all spans created by the review service.
+pub const TRACER_NAME: &str = "review";
+
+/// Extracts W3C trace context fields from an HTTP header map.
+pub struct HeaderExtractor<'a>(pub &'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    /// Returns the value of a header as string, if it exists and is valid ASCII.
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    /// Returns all header names.
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
+
+/// Extracts the remote parent context from a carrier using the global text map propagator.
+///
+/// * `carrier` - Carrier containing the W3C `traceparent` and `tracestate` fields.
+pub fn extract_context(carrier: &dyn Extractor) -> Context {
+    global::get_text_map_propagator(|propagator| propagator.extract(carrier))
+}
+
+/// Starts a consumer span for a received Dapr event and returns the context containing it.
+///
+/// * `parent_context` - Remote context extracted from the event or its HTTP request.
+/// * `topic` - Topic the event was published on.
+pub fn start_event_span(parent_context: &Context, topic: &str) -> Context {
+    let tracer = global::tracer(TRACER_NAME);
+    let span = tracer
+        .span_builder(format!("{} process", topic))
+        .with_kind(SpanKind::Consumer)
+        .with_attributes(vec![
+            KeyValue::new("messaging.system", "dapr"),
+            KeyValue::new("messaging.destination.name", topic.to_string()),
+            KeyValue::new("messaging.operation.type", "process"),
+        ])
+        .start_with_context(&tracer, parent_context);
+    parent_context.with_span(span)
+}